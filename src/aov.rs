@@ -0,0 +1,176 @@
+use crate::*;
+
+// Arbitrary output variables: extra passes recorded alongside the beauty image for compositing
+// and denoising.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    ObjectId,
+    Direct,
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 7] = [
+        Self::Albedo,
+        Self::Normal,
+        Self::Depth,
+        Self::Position,
+        Self::ObjectId,
+        Self::Direct,
+        Self::Indirect,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "albedo" => Some(Self::Albedo),
+            "normal" => Some(Self::Normal),
+            "depth" => Some(Self::Depth),
+            "position" => Some(Self::Position),
+            "object-id" => Some(Self::ObjectId),
+            "direct" => Some(Self::Direct),
+            "indirect" => Some(Self::Indirect),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Albedo => "albedo",
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Position => "position",
+            Self::ObjectId => "object-id",
+            Self::Direct => "direct",
+            Self::Indirect => "indirect",
+        }
+    }
+
+    // OpenEXR channel names for the pass, in the order of the colour components that hold them.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Self::Albedo | Self::Direct | Self::Indirect => &["R", "G", "B"],
+            Self::Normal | Self::Position => &["X", "Y", "Z"],
+            Self::Depth => &["Z"],
+            Self::ObjectId => &["id"],
+        }
+    }
+}
+
+// What one camera sample saw, filled in by the integrator alongside its radiance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovSample {
+    pub hit: bool,
+    pub albedo: Colour,
+    pub normal: Vec3,
+    pub depth: f64,
+    pub position: Point3,
+    pub object_id: u32,
+    pub direct: Colour,
+    pub indirect: Colour,
+}
+
+impl AovSample {
+    pub fn new() -> Self {
+        Self {
+            hit: false,
+            albedo: Colour::zero(),
+            normal: Vec3::zero(),
+            depth: 0.0,
+            position: Point3::zero(),
+            object_id: 0,
+            direct: Colour::zero(),
+            indirect: Colour::zero(),
+        }
+    }
+
+    // Records the geometry of the camera ray's first hit. Camera rays are not unit length, so
+    // `rec.t` is scaled to a distance.
+    pub fn record_hit(&mut self, ray: &Ray, rec: &HitRecord) {
+        self.hit = true;
+        self.normal = rec.normal;
+        self.depth = rec.t * ray.direction().length();
+        self.position = rec.p;
+        self.object_id = rec.object_id;
+    }
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Running sums of a pixel's AOV samples. The geometric passes average over the samples that hit
+// something, and the object ID is that of the first sample to hit anything.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovPixel {
+    pub albedo: Colour,
+    pub normal: Vec3,
+    pub depth: f64,
+    pub position: Point3,
+    pub direct: Colour,
+    pub indirect: Colour,
+    pub object_id: u32,
+    pub hits: u32,
+    pub count: u32,
+}
+
+impl AovPixel {
+    pub fn new() -> Self {
+        Self {
+            albedo: Colour::zero(),
+            normal: Vec3::zero(),
+            depth: 0.0,
+            position: Point3::zero(),
+            direct: Colour::zero(),
+            indirect: Colour::zero(),
+            object_id: 0,
+            hits: 0,
+            count: 0,
+        }
+    }
+
+    pub fn add(&mut self, sample: &AovSample) {
+        self.albedo += sample.albedo;
+        self.direct += sample.direct;
+        self.indirect += sample.indirect;
+        self.count += 1;
+
+        if sample.hit {
+            if self.hits == 0 {
+                self.object_id = sample.object_id;
+            }
+            self.normal += sample.normal;
+            self.depth += sample.depth;
+            self.position += sample.position;
+            self.hits += 1;
+        }
+    }
+
+    // The pass's value for the pixel. Scalar passes fill all three components; pixels where
+    // nothing was hit have infinite depth and zero normal, position and object ID.
+    pub fn value(&self, aov: Aov) -> Colour {
+        let mean = |sum: Vec3, n: u32| if n == 0 { sum } else { sum / n as f64 };
+        match aov {
+            Aov::Albedo => mean(self.albedo, self.count),
+            Aov::Direct => mean(self.direct, self.count),
+            Aov::Indirect => mean(self.indirect, self.count),
+            Aov::Normal if self.normal.near_zero() => Vec3::zero(),
+            Aov::Normal => self.normal.unit_vector(),
+            Aov::Position => mean(self.position, self.hits),
+            Aov::Depth if self.hits == 0 => Colour::one() * f64::INFINITY,
+            Aov::Depth => Colour::one() * (self.depth / self.hits as f64),
+            Aov::ObjectId => Colour::one() * self.object_id as f64,
+        }
+    }
+}
+
+impl Default for AovPixel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -72,16 +72,23 @@ impl BVHNode {
 }
 
 impl Hittable for BVHNode {
-    fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         if !self.bbox.hit(ray, ray_t) {
             return false;
         }
 
-        let hit_left = self.left.hit(ray, ray_t, rec);
+        let hit_left = self.left.hit(ray, ray_t, rec, sampler);
         let hit_right = self.right.hit(
             ray,
             Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max }),
             rec,
+            sampler,
         );
 
         hit_left || hit_right
@@ -90,4 +97,36 @@ impl Hittable for BVHNode {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // The children's densities summed, for meshes whose faces each give their share of a
+    // density over the whole mesh. A node over a single object holds it as both children.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        if !self.bbox.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
+            return 0.0;
+        }
+
+        let left = self.left.pdf_value(origin, direction);
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return left;
+        }
+        left + self.right.pdf_value(origin, direction)
+    }
+
+    // This node, plus those below it that `hit` goes on to test; the right child only sees the
+    // ray up to the left child's hit.
+    fn bvh_nodes_visited(&self, ray: &Ray, ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        if !self.bbox.hit(ray, ray_t) {
+            return 1;
+        }
+
+        let mut rec = HitRecord::new();
+        let left = self.left.bvh_nodes_visited(ray, ray_t, sampler);
+        let right_t = if self.left.hit(ray, ray_t, &mut rec, sampler) {
+            Interval::new(ray_t.min, rec.t)
+        } else {
+            ray_t
+        };
+        1 + left + self.right.bvh_nodes_visited(ray, right_t, sampler)
+    }
 }
@@ -1,5 +1,12 @@
 use crate::*;
-use rayon::iter::ParallelIterator;
+use rayon::prelude::*;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct Camera {
     pub aspect_ratio: f64,
@@ -7,7 +14,6 @@ pub struct Camera {
     image_height: u32,
     pub max_depth: u32,
     pub samples_per_pixel: u32,
-    pixel_samples_scale: f64,
     camera_centre: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
@@ -24,6 +30,20 @@ pub struct Camera {
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
     pub background: Colour,
+    pub display_transform: DisplayTransform,
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    pub seed: u64,
+    pub sampler: Arc<dyn PixelSampler>,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub termination: Termination,
+    pub lights: Option<Arc<dyn Hittable>>,
+    pub integrator: Arc<dyn Integrator>,
+    // Reconstruction filter the samples are splatted through. Without one, each pixel is the
+    // plain average of its own samples.
+    pub filter: Option<Arc<dyn Filter>>,
+    // Passes to record alongside the beauty image; recording is skipped when empty.
+    pub aovs: Vec<Aov>,
 }
 
 impl Camera {
@@ -41,8 +61,6 @@ impl Camera {
         focus_distance: f64,
         background: Colour,
     ) -> Self {
-        let pixel_samples_scale = 1.0 / samples_per_pixel as f64;
-
         let mut image_height: u32 = (image_width as f64 / aspect_ratio) as u32;
         image_height = if image_height < 1 { 1 } else { image_height };
 
@@ -81,7 +99,6 @@ impl Camera {
             image_height,
             max_depth,
             samples_per_pixel,
-            pixel_samples_scale,
             camera_centre,
             pixel00_loc,
             pixel_delta_u,
@@ -98,11 +115,27 @@ impl Camera {
             defocus_disk_u,
             defocus_disk_v,
             background,
+            display_transform: DisplayTransform::default(),
+            tile_size: 32,
+            tile_order: TileOrder::Scanline,
+            seed: 0,
+            sampler: Arc::new(IndependentSampler::new()),
+            adaptive: None,
+            termination: Termination::SampleCount,
+            lights: None,
+            integrator: Arc::new(PathIntegrator::default()),
+            filter: None,
+            aovs: Vec::new(),
         }
     }
 
-    pub fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let offset = Self::sample_square();
+    pub fn get_ray(&self, i: u32, j: u32, sampler: &mut dyn Sampler) -> Ray {
+        let offset = Self::sample_square(sampler);
+        self.ray_through(i, j, offset, sampler)
+    }
+
+    // A ray through the point `offset` pixels from the centre of pixel (i, j).
+    fn ray_through(&self, i: u32, j: u32, offset: Vec3, sampler: &mut dyn Sampler) -> Ray {
         let pixel_sample = self.pixel00_loc
             + self.pixel_delta_u * (i as f64 + offset.x())
             + self.pixel_delta_v * (j as f64 + offset.y());
@@ -110,63 +143,381 @@ impl Camera {
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.camera_centre
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler)
         };
         let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random_double();
+        let ray_time = sampler.get_1d();
 
         Ray::new(ray_origin, ray_direction, ray_time)
     }
 
-    fn sample_square() -> Vec3 {
-        Vec3::new(random_double() - 0.5, random_double() - 0.5, 0.0)
+    fn sample_square(sampler: &mut dyn Sampler) -> Vec3 {
+        let (x, y) = sampler.get_2d();
+        Vec3::new(x - 0.5, y - 0.5, 0.0)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
+    fn defocus_disk_sample(&self, sampler: &mut dyn Sampler) -> Point3 {
+        let p = Vec3::random_in_unit_disk(sampler);
         self.camera_centre + (self.defocus_disk_u * p[0]) + (self.defocus_disk_v * p[1])
     }
 
-    pub fn ray_colour(&self, ray: Ray, depth: u32, world: &dyn Hittable) -> Colour {
-        if depth == 0 {
-            return Colour::zero();
+    pub fn ray_colour(
+        &self,
+        ray: Ray,
+        depth: u32,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+    ) -> Colour {
+        let scene = Scene {
+            max_depth: depth,
+            ..self.scene(world)
+        };
+        self.integrator.radiance(&ray, &scene, sampler, &mut 0)
+    }
+
+    fn scene<'a>(&'a self, world: &'a dyn Hittable) -> Scene<'a> {
+        Scene {
+            world,
+            lights: self.lights.as_deref(),
+            background: self.background,
+            max_depth: self.max_depth,
         }
+    }
 
-        let mut rec = HitRecord::new();
+    pub fn image_height(&self) -> u32 {
+        self.image_height
+    }
 
-        if !world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
-            return self.background;
+    #[allow(clippy::too_many_arguments)]
+    fn sample_pixel(
+        &self,
+        world: &dyn Hittable,
+        i: u32,
+        j: u32,
+        samples: u32,
+        pixel: &mut PixelSamples,
+        mut film: Option<&mut Film>,
+        rays: &mut u64,
+    ) {
+        let index = j as u64 * self.image_width as u64 + i as u64;
+        let scene = self.scene(world);
+        let mut sampler = self.sampler.clone_box();
+        for _ in 0..samples {
+            sampler.start_pixel_sample(self.seed, index, pixel.count as u64);
+            let offset = Self::sample_square(sampler.as_mut());
+            let ray = self.ray_through(i, j, offset, sampler.as_mut());
+            let colour = if self.aovs.is_empty() {
+                self.integrator
+                    .radiance(&ray, &scene, sampler.as_mut(), rays)
+            } else {
+                let mut aov = AovSample::new();
+                let colour = self.integrator.radiance_with_aovs(
+                    &ray,
+                    &scene,
+                    sampler.as_mut(),
+                    rays,
+                    &mut aov,
+                );
+                pixel.aov.get_or_insert_default().add(&aov);
+                colour
+            };
+            pixel.add(colour);
+            if let (Some(film), Some(filter)) = (film.as_deref_mut(), &self.filter) {
+                film.splat(
+                    filter.as_ref(),
+                    i as f64 + offset.x(),
+                    j as f64 + offset.y(),
+                    colour,
+                );
+            }
         }
+    }
 
-        let mut scattered = Ray::new(Point3::zero(), Vec3::zero(), 0.0);
-        let mut attenuation = Colour::zero();
-        let colour_from_emission = rec.material.emitted(rec.u, rec.v, &rec.p);
+    // Film for the samples of pixels [x0, x1) x [y0, y1), if there is a filter to splat them
+    // through.
+    fn region_film(&self, pixels: (u32, u32, u32, u32)) -> Option<Film> {
+        self.filter.as_ref().map(|filter| {
+            Film::for_pixels(filter.as_ref(), pixels, self.image_width, self.image_height)
+        })
+    }
 
-        if !rec
-            .material
-            .scatter(&ray, &rec, &mut attenuation, &mut scattered)
-        {
-            return colour_from_emission;
+    pub fn new_accumulator(&self) -> Accumulator {
+        let mut accumulator = Accumulator::new(self.image_width, self.image_height);
+        if self.filter.is_some() {
+            accumulator.film = Some(Film::new(self.image_width, self.image_height));
         }
+        accumulator
+    }
 
-        let colour_from_scatter = attenuation * self.ray_colour(scattered, depth - 1, world);
+    // The samples a pixel still needs: all of them up to `samples_per_pixel`, or none once
+    // adaptive sampling finds its error estimate below the threshold.
+    fn remaining_samples(&self, pixel: &PixelSamples) -> u32 {
+        match self.adaptive {
+            Some(adaptive)
+                if pixel.count >= adaptive.min_samples
+                    && pixel.relative_error() <= adaptive.threshold =>
+            {
+                0
+            }
+            _ => self.samples_per_pixel.saturating_sub(pixel.count),
+        }
+    }
 
-        colour_from_emission + colour_from_scatter
+    // Adaptive pixels are sampled in batches of `min_samples`, re-checking the error between
+    // batches; the first batch brings them up to the minimum.
+    fn render_pixel(
+        &self,
+        world: &dyn Hittable,
+        i: u32,
+        j: u32,
+        pixel: &mut PixelSamples,
+        mut film: Option<&mut Film>,
+        rays: &mut u64,
+    ) {
+        loop {
+            let remaining = self.remaining_samples(pixel);
+            if remaining == 0 {
+                return;
+            }
+            let batch = self
+                .adaptive
+                .map_or(remaining, |adaptive| adaptive.min_samples.max(1))
+                .min(remaining);
+            self.sample_pixel(world, i, j, batch, pixel, film.as_deref_mut(), rays);
+        }
     }
 
-    pub fn render(&self, world: &dyn Hittable) {
-        let mut imgbuf = image::ImageBuffer::new(self.image_width, self.image_height);
+    pub fn render_framebuffer(&self, world: &dyn Hittable) -> Framebuffer {
+        self.render_tiles(world, |_| {}).resolve()
+    }
 
-        imgbuf.par_enumerate_pixels_mut().for_each(|(i, j, pixel)| {
-            let mut pixel_colour = Colour::zero();
-            for _ in 0..self.samples_per_pixel {
-                let ray = self.get_ray(i, j);
-                pixel_colour += self.ray_colour(ray, self.max_depth, world);
+    // Worker threads pull tiles in `tile_order` from a shared counter and send finished ones
+    // back, so `progress` runs on the calling thread and needs no synchronisation.
+    //
+    // A tile is sampled to completion before the next is handed out, which only the sample
+    // count can bound, so any other termination policy renders in passes instead and reports no
+    // tile progress.
+    pub fn render_tiles(
+        &self,
+        world: &dyn Hittable,
+        mut progress: impl FnMut(&TileProgress),
+    ) -> Accumulator {
+        if self.termination != Termination::SampleCount {
+            let settings = ProgressiveSettings {
+                samples_per_pass: 1,
+                preview_every_passes: None,
+                preview_interval: None,
+            };
+            return self.render_progressive(world, &settings, |_, _| ControlFlow::Continue(()));
+        }
+
+        let mut accumulator = self.new_accumulator();
+        let tiles = tiles(
+            self.image_width,
+            self.image_height,
+            self.tile_size,
+            self.tile_order,
+        );
+        let next_tile = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let start = Instant::now();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                rayon::scope(|workers| {
+                    for _ in 0..rayon::current_num_threads() {
+                        let sender = sender.clone();
+                        let tiles = &tiles;
+                        let next_tile = &next_tile;
+                        workers.spawn(move |_| {
+                            while let Some(tile) =
+                                tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+                            {
+                                let mut rays = 0;
+                                let mut pixels = Vec::with_capacity(tile.pixel_count());
+                                let mut film =
+                                    self.region_film((tile.x0, tile.y0, tile.x1, tile.y1));
+                                for j in tile.y0..tile.y1 {
+                                    for i in tile.x0..tile.x1 {
+                                        let mut pixel = PixelSamples::new();
+                                        self.render_pixel(
+                                            world,
+                                            i,
+                                            j,
+                                            &mut pixel,
+                                            film.as_mut(),
+                                            &mut rays,
+                                        );
+                                        pixels.push(pixel);
+                                    }
+                                }
+                                if sender.send((*tile, pixels, film, rays)).is_err() {
+                                    return;
+                                }
+                            }
+                        });
+                    }
+                });
+                drop(sender);
+            });
+
+            let mut done = TileProgress {
+                tiles_done: 0,
+                tiles_total: tiles.len(),
+                rays: 0,
+                elapsed: Duration::ZERO,
+            };
+            for (tile, pixels, film, rays) in receiver {
+                if let (Some(accumulated), Some(film)) = (&mut accumulator.film, &film) {
+                    accumulated.merge(film);
+                }
+                let mut pixels = pixels.into_iter();
+                for j in tile.y0..tile.y1 {
+                    for i in tile.x0..tile.x1 {
+                        let index = j as usize * self.image_width as usize + i as usize;
+                        accumulator.pixels[index] = pixels.next().unwrap();
+                    }
+                }
+                done.tiles_done += 1;
+                done.rays += rays;
+                done.elapsed = start.elapsed();
+                progress(&done);
             }
-            write_colour(pixel, pixel_colour * self.pixel_samples_scale);
         });
 
-        imgbuf.save("scene.png").expect("Oops...");
+        accumulator
+    }
+
+    pub fn render_progressive(
+        &self,
+        world: &dyn Hittable,
+        settings: &ProgressiveSettings,
+        preview: impl FnMut(&RenderProgress, &Accumulator) -> ControlFlow<()>,
+    ) -> Accumulator {
+        self.progressive_passes(world, settings, self.new_accumulator(), preview)
+    }
+
+    pub fn resume_progressive(
+        &self,
+        world: &dyn Hittable,
+        settings: &ProgressiveSettings,
+        accumulator: Accumulator,
+        preview: impl FnMut(&RenderProgress, &Accumulator) -> ControlFlow<()>,
+    ) -> Result<Accumulator, ImageSizeMismatch> {
+        let camera = (self.image_width, self.image_height);
+        let found = (accumulator.width, accumulator.height);
+        if found != camera {
+            return Err(ImageSizeMismatch { camera, found });
+        }
+        Ok(self.progressive_passes(world, settings, accumulator, preview))
+    }
+
+    fn progressive_passes(
+        &self,
+        world: &dyn Hittable,
+        settings: &ProgressiveSettings,
+        mut accumulator: Accumulator,
+        mut preview: impl FnMut(&RenderProgress, &Accumulator) -> ControlFlow<()>,
+    ) -> Accumulator {
+        let width = self.image_width as usize;
+        let samples_per_pass = settings.samples_per_pass.max(1);
+
+        let start = Instant::now();
+        let mut last_preview = start;
+        let mut passes_since_preview = 0;
+        let mut pass = 0;
+
+        while self.min_unfinished_samples(&accumulator).is_some()
+            && !self.termination.is_met(&accumulator, start.elapsed())
+        {
+            // Rows are sampled in parallel, each splatting into its own strip of film that is
+            // merged as soon as the row is done, so only the strips in flight are ever held.
+            let film = accumulator.film.take().map(Mutex::new);
+            accumulator
+                .pixels
+                .par_chunks_mut(width)
+                .enumerate()
+                .for_each(|(j, row)| {
+                    let j = j as u32;
+                    let mut strip = self.region_film((0, j, self.image_width, j + 1));
+                    for (i, pixel) in row.iter_mut().enumerate() {
+                        let samples = samples_per_pass.min(self.remaining_samples(pixel));
+                        self.sample_pixel(
+                            world,
+                            i as u32,
+                            j,
+                            samples,
+                            pixel,
+                            strip.as_mut(),
+                            &mut 0,
+                        );
+                    }
+                    if let (Some(film), Some(strip)) = (&film, &strip) {
+                        film.lock().unwrap().merge(strip);
+                    }
+                });
+            accumulator.film = film.map(|film| film.into_inner().unwrap());
+
+            pass += 1;
+            passes_since_preview += 1;
+
+            let unfinished = self.min_unfinished_samples(&accumulator);
+            let due = settings
+                .preview_every_passes
+                .is_some_and(|n| passes_since_preview >= n)
+                || settings
+                    .preview_interval
+                    .is_some_and(|interval| last_preview.elapsed() >= interval);
+
+            let finished = self.termination.is_met(&accumulator, start.elapsed());
+            if let Some(samples_per_pixel) = unfinished.filter(|_| due && !finished) {
+                let progress = RenderProgress {
+                    pass,
+                    samples_per_pixel,
+                    target_samples_per_pixel: self.samples_per_pixel,
+                    elapsed: start.elapsed(),
+                };
+                if preview(&progress, &accumulator).is_break() {
+                    break;
+                }
+                last_preview = Instant::now();
+                passes_since_preview = 0;
+            }
+        }
+
+        accumulator
+    }
+
+    // The lowest sample count among pixels that still need samples, or None once all are done.
+    fn min_unfinished_samples(&self, accumulator: &Accumulator) -> Option<u32> {
+        accumulator
+            .pixels
+            .iter()
+            .filter(|pixel| self.remaining_samples(pixel) > 0)
+            .map(|pixel| pixel.count)
+            .min()
+    }
+
+    pub fn render(
+        &self,
+        world: &dyn Hittable,
+        path: impl AsRef<Path>,
+        format: OutputFormat,
+    ) -> Result<(), OutputError> {
+        let accumulator = self.render_tiles(world, |_| {});
+        let metadata = [(
+            "Samples per pixel".to_string(),
+            accumulator.samples_summary(),
+        )];
+        save_render(
+            &accumulator.resolve(),
+            &accumulator,
+            path.as_ref(),
+            format,
+            &self.display_transform,
+            &self.aovs,
+            &metadata,
+        )
     }
 }
 
@@ -187,3 +538,85 @@ impl Default for Camera {
         )
     }
 }
+
+// An accumulator handed to `resume_progressive` that was sized for a different image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageSizeMismatch {
+    pub camera: (u32, u32),
+    pub found: (u32, u32),
+}
+
+impl fmt::Display for ImageSizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot resume a {}x{} render with a {}x{} camera",
+            self.found.0, self.found.1, self.camera.0, self.camera.1
+        )
+    }
+}
+
+impl std::error::Error for ImageSizeMismatch {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressiveSettings {
+    pub samples_per_pass: u32,
+    pub preview_every_passes: Option<u32>,
+    pub preview_interval: Option<Duration>,
+}
+
+impl Default for ProgressiveSettings {
+    fn default() -> Self {
+        Self {
+            samples_per_pass: 1,
+            preview_every_passes: None,
+            preview_interval: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+// When a render stops. Every policy still stops at `samples_per_pixel`; the time budget and
+// error target are checked between passes, so tiled renders fall back to passes for them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    SampleCount,
+    TimeBudget(Duration),
+    // The mean over all pixels of each pixel's relative error (see `PixelSamples`).
+    ErrorTarget(f64),
+}
+
+impl Termination {
+    pub fn is_met(&self, accumulator: &Accumulator, elapsed: Duration) -> bool {
+        match *self {
+            Self::SampleCount => false,
+            Self::TimeBudget(budget) => elapsed >= budget,
+            Self::ErrorTarget(target) => accumulator.mean_relative_error() <= target,
+        }
+    }
+}
+
+// Stops sampling a pixel once the standard error of its mean luminance, relative to that mean,
+// drops to `threshold`. Every pixel takes at least `min_samples` and at most the camera's
+// `samples_per_pixel`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub threshold: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            threshold: 0.01,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderProgress {
+    pub pass: u32,
+    pub samples_per_pixel: u32,
+    pub target_samples_per_pixel: u32,
+    pub elapsed: Duration,
+}
@@ -0,0 +1,421 @@
+use crate::*;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"RTWCKPT\0";
+const VERSION: u32 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub scene: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub seed: u64,
+    pub aovs: bool,
+    // Name and radius of the reconstruction filter, or "none".
+    pub filter: String,
+    // Name of the integrator and any clamping or regularisation it applies.
+    pub integrator: String,
+    // Name of the sampler, with the sample count stratified sampling sizes its strata from.
+    pub sampler: String,
+    // Adaptive sampling's threshold and minimum sample count, or "none".
+    pub adaptive: String,
+}
+
+impl RenderSettings {
+    pub fn new(scene: &str, cam: &Camera) -> Self {
+        Self {
+            scene: scene.to_string(),
+            image_width: cam.image_width,
+            image_height: cam.image_height(),
+            samples_per_pixel: cam.samples_per_pixel,
+            max_depth: cam.max_depth,
+            seed: cam.seed,
+            aovs: !cam.aovs.is_empty(),
+            filter: cam.filter.as_ref().map_or("none".to_string(), |filter| {
+                format!("{} {}", filter.name(), filter.radius())
+            }),
+            integrator: cam.integrator.description(),
+            sampler: cam.sampler.description(),
+            adaptive: cam.adaptive.map_or("none".to_string(), |adaptive| {
+                format!(
+                    "threshold {} min-samples {}",
+                    adaptive.threshold, adaptive.min_samples
+                )
+            }),
+        }
+    }
+
+    // The sample target may grow between runs; anything that changes the image itself may not.
+    pub fn check_resumable(&self, other: &RenderSettings) -> Result<(), String> {
+        if self.scene != other.scene {
+            return Err(format!(
+                "checkpoint is for scene '{}', not '{}'",
+                self.scene, other.scene
+            ));
+        }
+        if (self.image_width, self.image_height) != (other.image_width, other.image_height) {
+            return Err(format!(
+                "checkpoint is {}x{}, not {}x{}",
+                self.image_width, self.image_height, other.image_width, other.image_height
+            ));
+        }
+        if self.seed != other.seed {
+            return Err(format!(
+                "checkpoint uses seed {}, not {}",
+                self.seed, other.seed
+            ));
+        }
+        if self.max_depth != other.max_depth {
+            return Err(format!(
+                "checkpoint uses max depth {}, not {}",
+                self.max_depth, other.max_depth
+            ));
+        }
+        if self.filter != other.filter {
+            return Err(format!(
+                "checkpoint uses filter '{}', not '{}'",
+                self.filter, other.filter
+            ));
+        }
+        if self.integrator != other.integrator {
+            return Err(format!(
+                "checkpoint uses integrator '{}', not '{}'",
+                self.integrator, other.integrator
+            ));
+        }
+        if self.sampler != other.sampler {
+            return Err(format!(
+                "checkpoint uses sampler '{}', not '{}'",
+                self.sampler, other.sampler
+            ));
+        }
+        if self.adaptive != other.adaptive {
+            return Err(format!(
+                "checkpoint uses adaptive sampling '{}', not '{}'",
+                self.adaptive, other.adaptive
+            ));
+        }
+        if self.aovs != other.aovs {
+            return Err(if self.aovs {
+                "checkpoint records AOVs, but none were requested".to_string()
+            } else {
+                "checkpoint has no AOVs to resume".to_string()
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub settings: RenderSettings,
+    pub accumulator: Accumulator,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(PathBuf, io::Error),
+    Invalid(PathBuf, String),
+    Mismatch(PathBuf, String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "checkpoint '{}': {}", path.display(), err),
+            Self::Invalid(path, msg) => {
+                write!(f, "checkpoint '{}' is invalid: {}", path.display(), msg)
+            }
+            Self::Mismatch(path, msg) => {
+                write!(f, "cannot resume from '{}': {}", path.display(), msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Checkpoint {
+    pub fn new(settings: RenderSettings, accumulator: Accumulator) -> Self {
+        Self {
+            settings,
+            accumulator,
+        }
+    }
+
+    // Writes to a sibling temporary file first so a render killed mid-save keeps the previous
+    // checkpoint intact.
+    pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let io_err = |err| CheckpointError::Io(path.to_path_buf(), err);
+        let file = File::create(&tmp).map_err(io_err)?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer).map_err(io_err)?;
+        writer
+            .into_inner()
+            .map_err(|err| io_err(err.into_error()))?
+            .sync_all()
+            .map_err(io_err)?;
+        fs::rename(&tmp, path).map_err(io_err)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        Self::load_checked(path, |_| Ok(()))
+    }
+
+    pub fn resume(path: &Path, settings: &RenderSettings) -> Result<Self, CheckpointError> {
+        Self::load_checked(path, |saved| saved.check_resumable(settings))
+    }
+
+    // The settings are read and checked first, and the pixels only once the file is known to be
+    // exactly as long as its image size implies, so a stray or corrupt file cannot ask for an
+    // allocation it does not back.
+    fn load_checked(
+        path: &Path,
+        check: impl FnOnce(&RenderSettings) -> Result<(), String>,
+    ) -> Result<Self, CheckpointError> {
+        let io_err = |err| CheckpointError::Io(path.to_path_buf(), err);
+        let read_err = |err: io::Error| match err.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                CheckpointError::Invalid(path.to_path_buf(), err.to_string())
+            }
+            _ => CheckpointError::Io(path.to_path_buf(), err),
+        };
+
+        let file = File::open(path).map_err(io_err)?;
+        let len = file.metadata().map_err(io_err)?.len();
+        let mut reader = BufReader::new(file);
+        let settings = read_settings(&mut reader).map_err(read_err)?;
+        check(&settings).map_err(|msg| CheckpointError::Mismatch(path.to_path_buf(), msg))?;
+
+        let header_len = reader.stream_position().map_err(io_err)?;
+        if pixel_data_len(&settings) != Some(len - header_len) {
+            return Err(CheckpointError::Invalid(
+                path.to_path_buf(),
+                format!(
+                    "file length does not match a {}x{} image",
+                    settings.image_width, settings.image_height
+                ),
+            ));
+        }
+        let accumulator = read_accumulator(&mut reader, &settings).map_err(read_err)?;
+        Ok(Self::new(settings, accumulator))
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        let s = &self.settings;
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        for text in [&s.scene, &s.filter, &s.integrator, &s.sampler, &s.adaptive] {
+            w.write_all(&(text.len() as u32).to_le_bytes())?;
+            w.write_all(text.as_bytes())?;
+        }
+        for value in [
+            s.image_width,
+            s.image_height,
+            s.samples_per_pixel,
+            s.max_depth,
+        ] {
+            w.write_all(&value.to_le_bytes())?;
+        }
+        w.write_all(&s.seed.to_le_bytes())?;
+        w.write_all(&[s.aovs as u8])?;
+
+        for pixel in &self.accumulator.pixels {
+            for c in 0..3 {
+                w.write_all(&pixel.sum[c].to_le_bytes())?;
+            }
+            w.write_all(&pixel.sum_sq.to_le_bytes())?;
+            w.write_all(&pixel.count.to_le_bytes())?;
+            if s.aovs {
+                write_aov_pixel(w, pixel.aov.as_deref().unwrap_or(&AovPixel::new()))?;
+            }
+        }
+
+        // The film always covers the whole image, so only its sums need storing.
+        if let Some(film) = &self.accumulator.film {
+            for pixel in &film.pixels {
+                for value in pixel.sum.iter().chain([&pixel.weight]) {
+                    w.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_settings(r: &mut impl Read) -> io::Result<RenderSettings> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a checkpoint file"));
+    }
+    if read_u32(r)? != VERSION {
+        return Err(invalid("unsupported checkpoint version"));
+    }
+
+    let scene = read_string(r, "scene name")?;
+    let filter = read_string(r, "filter name")?;
+    let integrator = read_string(r, "integrator name")?;
+    let sampler = read_string(r, "sampler name")?;
+    let adaptive = read_string(r, "adaptive sampling settings")?;
+
+    Ok(RenderSettings {
+        scene,
+        filter,
+        integrator,
+        sampler,
+        adaptive,
+        image_width: read_u32(r)?,
+        image_height: read_u32(r)?,
+        samples_per_pixel: read_u32(r)?,
+        max_depth: read_u32(r)?,
+        seed: read_u64(r)?,
+        aovs: read_u8(r)? != 0,
+    })
+}
+
+// The bytes `write` stores after the settings: every pixel's samples, then the film if there is
+// one. None if that overflows.
+fn pixel_data_len(settings: &RenderSettings) -> Option<u64> {
+    const PIXEL: u64 = 4 * 8 + 4;
+    const AOV_PIXEL: u64 = 5 * 3 * 8 + 8 + 3 * 4;
+    const FILM_PIXEL: u64 = 4 * 16;
+
+    let mut per_pixel = PIXEL;
+    if settings.aovs {
+        per_pixel += AOV_PIXEL;
+    }
+    if settings.filter != "none" {
+        per_pixel += FILM_PIXEL;
+    }
+    (settings.image_width as u64)
+        .checked_mul(settings.image_height as u64)?
+        .checked_mul(per_pixel)
+}
+
+fn read_accumulator(r: &mut impl Read, settings: &RenderSettings) -> io::Result<Accumulator> {
+    let mut accumulator = Accumulator::new(settings.image_width, settings.image_height);
+    for pixel in &mut accumulator.pixels {
+        for c in 0..3 {
+            pixel.sum[c] = read_f64(r)?;
+        }
+        pixel.sum_sq = read_f64(r)?;
+        pixel.count = read_u32(r)?;
+        if settings.aovs {
+            pixel.aov = Some(Box::new(read_aov_pixel(r)?));
+        }
+    }
+
+    if settings.filter != "none" {
+        let mut film = Film::new(settings.image_width, settings.image_height);
+        for pixel in &mut film.pixels {
+            for c in 0..3 {
+                pixel.sum[c] = read_i128(r)?;
+            }
+            pixel.weight = read_i128(r)?;
+        }
+        accumulator.film = Some(film);
+    }
+    Ok(accumulator)
+}
+
+fn write_aov_pixel(w: &mut impl Write, aov: &AovPixel) -> io::Result<()> {
+    for v in [
+        aov.albedo,
+        aov.normal,
+        aov.position,
+        aov.direct,
+        aov.indirect,
+    ] {
+        for c in 0..3 {
+            w.write_all(&v[c].to_le_bytes())?;
+        }
+    }
+    w.write_all(&aov.depth.to_le_bytes())?;
+    for value in [aov.object_id, aov.hits, aov.count] {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_aov_pixel(r: &mut impl Read) -> io::Result<AovPixel> {
+    let mut read_vec3 =
+        || -> io::Result<Vec3> { Ok(Vec3::new(read_f64(r)?, read_f64(r)?, read_f64(r)?)) };
+    let albedo = read_vec3()?;
+    let normal = read_vec3()?;
+    let position = read_vec3()?;
+    let direct = read_vec3()?;
+    let indirect = read_vec3()?;
+    Ok(AovPixel {
+        albedo,
+        normal,
+        position,
+        direct,
+        indirect,
+        depth: read_f64(r)?,
+        object_id: read_u32(r)?,
+        hits: read_u32(r)?,
+        count: read_u32(r)?,
+    })
+}
+
+// Reads through `take` so a bogus length fails at the end of the file rather than allocating
+// up front.
+fn read_string(r: &mut impl Read, what: &str) -> io::Result<String> {
+    let len = read_u32(r)? as u64;
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not UTF-8", what)))
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_i128(r: &mut impl Read) -> io::Result<i128> {
+    let mut bytes = [0u8; 16];
+    r.read_exact(&mut bytes)?;
+    Ok(i128::from_le_bytes(bytes))
+}
+
+fn read_f64(r: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
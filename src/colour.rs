@@ -1,21 +1,247 @@
-use crate::{Interval, vec3::*};
+use crate::{Arc, Interval, vec3::*};
 use image::Rgb;
 
 pub type Colour = Vec3;
 
-pub fn linear_to_gamma(x: f64) -> f64 {
-    x.sqrt()
+pub fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn luminance(colour: Colour) -> f64 {
+    0.2126 * colour.x() + 0.7152 * colour.y() + 0.0722 * colour.z()
+}
+
+// False colour for a value in [0, 1], from black through blue, red and yellow to white.
+pub fn heat_colour(t: f64) -> Colour {
+    const RAMP: [(f64, f64, f64); 5] = [
+        (0.0, 0.0, 0.0),
+        (0.0, 0.0, 1.0),
+        (1.0, 0.0, 0.0),
+        (1.0, 1.0, 0.0),
+        (1.0, 1.0, 1.0),
+    ];
+
+    let x = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64;
+    let k = (x as usize).min(RAMP.len() - 2);
+    let f = x - k as f64;
+    let (a, b) = (RAMP[k], RAMP[k + 1]);
+    Colour::new(
+        srgb_to_linear(a.0 + (b.0 - a.0) * f),
+        srgb_to_linear(a.1 + (b.1 - a.1) * f),
+        srgb_to_linear(a.2 + (b.2 - a.2) * f),
+    )
+}
+
+pub trait ToneMapper: Send + Sync {
+    fn tone_map(&self, colour: Colour) -> Colour;
+}
+
+pub fn tone_mapper_from_name(name: &str) -> Option<Arc<dyn ToneMapper>> {
+    match name.to_ascii_lowercase().as_str() {
+        "clamp" | "none" => Some(Arc::new(Clamp)),
+        "reinhard" => Some(Arc::new(Reinhard)),
+        "reinhard-extended" => Some(Arc::new(ExtendedReinhard::default())),
+        "hable" | "uncharted2" => Some(Arc::new(Hable::default())),
+        "aces" => Some(Arc::new(AcesFilmic)),
+        _ => None,
+    }
+}
+
+pub const TONE_MAPPER_NAMES: [&str; 5] =
+    ["clamp", "reinhard", "reinhard-extended", "hable", "aces"];
+
+// Leaves the colour alone; anything above 1.0 is clipped when quantising.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clamp;
+
+impl ToneMapper for Clamp {
+    fn tone_map(&self, colour: Colour) -> Colour {
+        colour
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reinhard;
+
+impl ToneMapper for Reinhard {
+    fn tone_map(&self, colour: Colour) -> Colour {
+        let l_in = luminance(colour);
+        if l_in <= 0.0 {
+            return colour;
+        }
+        colour * (1.0 / (1.0 + l_in))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtendedReinhard {
+    pub white_point: f64,
+}
+
+impl ExtendedReinhard {
+    pub fn new(white_point: f64) -> Self {
+        Self { white_point }
+    }
+}
+
+impl Default for ExtendedReinhard {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl ToneMapper for ExtendedReinhard {
+    fn tone_map(&self, colour: Colour) -> Colour {
+        let l_in = luminance(colour);
+        if l_in <= 0.0 {
+            return colour;
+        }
+        let l_out = l_in * (1.0 + l_in / (self.white_point * self.white_point)) / (1.0 + l_in);
+        colour * (l_out / l_in)
+    }
+}
+
+// John Hable's filmic curve from Uncharted 2.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hable {
+    pub exposure_bias: f64,
+    pub white_point: f64,
+}
+
+impl Hable {
+    pub fn new(exposure_bias: f64, white_point: f64) -> Self {
+        Self {
+            exposure_bias,
+            white_point,
+        }
+    }
+
+    fn partial(x: f64) -> f64 {
+        let a = 0.15;
+        let b = 0.50;
+        let c = 0.10;
+        let d = 0.20;
+        let e = 0.02;
+        let f = 0.30;
+        ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+    }
+}
+
+impl Default for Hable {
+    fn default() -> Self {
+        Self::new(2.0, 11.2)
+    }
+}
+
+impl ToneMapper for Hable {
+    fn tone_map(&self, colour: Colour) -> Colour {
+        let white_scale = 1.0 / Self::partial(self.white_point);
+        let c = colour * self.exposure_bias;
+        Colour::new(
+            Self::partial(c.x()),
+            Self::partial(c.y()),
+            Self::partial(c.z()),
+        ) * white_scale
+    }
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AcesFilmic;
+
+impl AcesFilmic {
+    fn mul(m: [[f64; 3]; 3], c: Colour) -> Colour {
+        Colour::new(
+            m[0][0] * c.x() + m[0][1] * c.y() + m[0][2] * c.z(),
+            m[1][0] * c.x() + m[1][1] * c.y() + m[1][2] * c.z(),
+            m[2][0] * c.x() + m[2][1] * c.y() + m[2][2] * c.z(),
+        )
+    }
+
+    fn rrt_and_odt_fit(x: f64) -> f64 {
+        let a = x * (x + 0.0245786) - 0.000090537;
+        let b = x * (0.983729 * x + 0.4329510) + 0.238081;
+        a / b
+    }
+}
+
+impl ToneMapper for AcesFilmic {
+    fn tone_map(&self, colour: Colour) -> Colour {
+        const INPUT: [[f64; 3]; 3] = [
+            [0.59719, 0.35458, 0.04823],
+            [0.07600, 0.90834, 0.01566],
+            [0.02840, 0.13383, 0.83777],
+        ];
+        const OUTPUT: [[f64; 3]; 3] = [
+            [1.60475, -0.53108, -0.07367],
+            [-0.10208, 1.10813, -0.00605],
+            [-0.00327, -0.07276, 1.07602],
+        ];
+
+        let c = Self::mul(INPUT, colour);
+        let c = Colour::new(
+            Self::rrt_and_odt_fit(c.x()),
+            Self::rrt_and_odt_fit(c.y()),
+            Self::rrt_and_odt_fit(c.z()),
+        );
+        Self::mul(OUTPUT, c)
+    }
+}
+
+#[derive(Clone)]
+pub struct DisplayTransform {
+    pub exposure: f64,
+    pub tone_mapper: Arc<dyn ToneMapper>,
+}
+
+impl DisplayTransform {
+    pub fn new(exposure: f64, tone_mapper: Arc<dyn ToneMapper>) -> Self {
+        Self {
+            exposure,
+            tone_mapper,
+        }
+    }
+
+    pub fn apply(&self, pixel_colour: Colour) -> Colour {
+        let exposed = pixel_colour * 2f64.powf(self.exposure);
+        let mapped = self.tone_mapper.tone_map(exposed);
+        let unit = Interval::new(0.0, 1.0);
+        Colour::new(
+            linear_to_srgb(unit.clamp(mapped.x())),
+            linear_to_srgb(unit.clamp(mapped.y())),
+            linear_to_srgb(unit.clamp(mapped.z())),
+        )
+    }
+
+    pub fn write_colour(&self, pixel: &mut Rgb<u8>, pixel_colour: Colour) {
+        let c = self.apply(pixel_colour);
+        let intensity = Interval::new(0.0, 0.999);
+        let ir = (256.0 * intensity.clamp(c.x())) as u8;
+        let ig = (256.0 * intensity.clamp(c.y())) as u8;
+        let ib = (256.0 * intensity.clamp(c.z())) as u8;
+
+        *pixel = Rgb([ir, ig, ib]);
+    }
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self::new(0.0, Arc::new(Clamp))
+    }
 }
 
 pub fn write_colour(pixel: &mut Rgb<u8>, pixel_colour: Colour) {
-    let r = linear_to_gamma(pixel_colour.x());
-    let g = linear_to_gamma(pixel_colour.y());
-    let b = linear_to_gamma(pixel_colour.z());
-    let inensity = Interval::new(0.0, 0.999);
-    let ir = (256.0 * inensity.clamp(r)) as u8;
-    let ig = (256.0 * inensity.clamp(g)) as u8;
-    let ib = (256.0 * inensity.clamp(b)) as u8;
-
-    // println!("{} {} {}", ir, ig, ib);
-    *pixel = Rgb([ir, ig, ib]);
+    DisplayTransform::default().write_colour(pixel, pixel_colour);
 }
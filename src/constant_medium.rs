@@ -6,6 +6,7 @@ pub struct ConstantMedium {
     boundary: Arc<dyn Hittable>,
     neg_inv_density: f64,
     phase_function: Arc<dyn Material>,
+    id: u32,
 }
 
 impl ConstantMedium {
@@ -17,6 +18,7 @@ impl ConstantMedium {
             boundary,
             neg_inv_density,
             phase_function,
+            id: next_object_id(),
         }
     }
 
@@ -28,26 +30,38 @@ impl ConstantMedium {
             boundary,
             neg_inv_density,
             phase_function,
+            id: next_object_id(),
         }
     }
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         let mut rec1 = HitRecord::new();
         let mut rec2 = HitRecord::new();
 
-        if !self.boundary.hit(r, Interval::universe(), &mut rec1) {
-            return false;
-        }
-
         if !self
             .boundary
-            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY), &mut rec2)
+            .hit(r, Interval::universe(), &mut rec1, sampler)
         {
             return false;
         }
 
+        if !self.boundary.hit(
+            r,
+            Interval::new(rec1.t + 0.0001, f64::INFINITY),
+            &mut rec2,
+            sampler,
+        ) {
+            return false;
+        }
+
         if rec1.t < ray_t.min {
             rec1.t = ray_t.min;
         }
@@ -65,7 +79,7 @@ impl Hittable for ConstantMedium {
 
         let ray_length = r.direction().length();
         let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-        let hit_distance = self.neg_inv_density * random_double().ln();
+        let hit_distance = self.neg_inv_density * sampler.get_1d().ln();
 
         if hit_distance > distance_inside_boundary {
             return false;
@@ -77,6 +91,8 @@ impl Hittable for ConstantMedium {
         rec.normal = Vec3::new(1.0, 0.0, 0.0);
         rec.front_face = true;
         rec.material = self.phase_function.clone();
+        rec.object_id = self.id;
+        rec.vertex_colour = Colour::one();
 
         true
     }
@@ -84,4 +100,20 @@ impl Hittable for ConstantMedium {
     fn bounding_box(&self) -> AABB {
         self.boundary.bounding_box()
     }
+
+    // `hit` looks for the boundary twice: where the ray enters it and, past that, where it leaves.
+    fn bvh_nodes_visited(&self, r: &Ray, _ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        let mut rec = HitRecord::new();
+        let mut nodes = self
+            .boundary
+            .bvh_nodes_visited(r, Interval::universe(), sampler);
+        if self
+            .boundary
+            .hit(r, Interval::universe(), &mut rec, sampler)
+        {
+            let exit_t = Interval::new(rec.t + 0.0001, f64::INFINITY);
+            nodes += self.boundary.bvh_nodes_visited(r, exit_t, sampler);
+        }
+        nodes
+    }
 }
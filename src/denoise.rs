@@ -0,0 +1,202 @@
+use crate::*;
+use rayon::prelude::*;
+
+// B3-spline taps of the 5x5 à-trous kernel.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// Edge-avoiding à-trous wavelet filter (Dammertz et al., 2010) with the variance-guided luminance
+// weights of SVGF (Schied et al., 2017). Each iteration spreads the same 5x5 kernel twice as far,
+// so five iterations cover a 125-pixel footprint.
+//
+// Albedo is divided out before filtering and multiplied back after, so texture detail survives;
+// the albedo and normal AOVs also stop the filter at material and geometry edges. Without AOVs
+// it falls back to the luminance weights alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    pub iterations: u32,
+    // How many standard deviations of a pixel's noise two luminances may differ by and still be
+    // blended.
+    pub sigma_luminance: f64,
+    // Exponent on the cosine between normals; higher keeps creases sharper.
+    pub sigma_normal: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_luminance: 4.0,
+            sigma_normal: 128.0,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+struct Guide {
+    albedo: Colour,
+    normal: Vec3,
+}
+
+impl Denoiser {
+    pub fn denoise(&self, accumulator: &Accumulator) -> Framebuffer {
+        let width = accumulator.width as usize;
+        let height = accumulator.height as usize;
+
+        let guides: Vec<Guide> = accumulator
+            .pixels
+            .iter()
+            .map(|pixel| match pixel.aov.as_deref() {
+                Some(aov) => Guide {
+                    albedo: demodulation_factor(aov.value(Aov::Albedo)),
+                    normal: aov.value(Aov::Normal),
+                },
+                None => Guide {
+                    albedo: Colour::one(),
+                    normal: Vec3::zero(),
+                },
+            })
+            .collect();
+
+        let mut illumination: Vec<Colour> = accumulator
+            .resolve()
+            .pixels
+            .iter()
+            .zip(&guides)
+            .map(|(colour, guide)| *colour / guide.albedo)
+            .collect();
+        // Unsampled pixels have no variance estimate; a large finite one lets them be filled in
+        // without poisoning the weighted sums.
+        let mut variance: Vec<f64> = accumulator
+            .pixels
+            .iter()
+            .zip(&guides)
+            .map(|(pixel, guide)| (pixel.variance() / luminance(guide.albedo).powi(2)).min(1e10))
+            .collect();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let filtered: Vec<(Colour, f64)> = (0..width * height)
+                .into_par_iter()
+                .map(|index| {
+                    self.filter_pixel(
+                        index % width,
+                        index / width,
+                        width,
+                        height,
+                        step,
+                        &illumination,
+                        &variance,
+                        &guides,
+                    )
+                })
+                .collect();
+            (illumination, variance) = filtered.into_iter().unzip();
+        }
+
+        Framebuffer {
+            width: accumulator.width,
+            height: accumulator.height,
+            pixels: illumination
+                .iter()
+                .zip(&guides)
+                .map(|(colour, guide)| *colour * guide.albedo)
+                .collect(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn filter_pixel(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        step: usize,
+        illumination: &[Colour],
+        variance: &[f64],
+        guides: &[Guide],
+    ) -> (Colour, f64) {
+        let p = y * width + x;
+        let luminance_p = luminance(illumination[p]);
+        let sigma = self.sigma_luminance * blurred_variance(x, y, width, height, variance).sqrt();
+        let guide_p = &guides[p];
+
+        let mut colour_sum = Colour::zero();
+        let mut weight_sum = 0.0;
+        let mut variance_sum = 0.0;
+
+        for (ky, hy) in KERNEL.iter().enumerate() {
+            let Some(qy) = (y + ky * step)
+                .checked_sub(2 * step)
+                .filter(|&qy| qy < height)
+            else {
+                continue;
+            };
+            for (kx, hx) in KERNEL.iter().enumerate() {
+                let Some(qx) = (x + kx * step)
+                    .checked_sub(2 * step)
+                    .filter(|&qx| qx < width)
+                else {
+                    continue;
+                };
+                let q = qy * width + qx;
+                let guide_q = &guides[q];
+
+                let luminance_distance = (luminance_p - luminance(illumination[q])).abs();
+                let w_luminance = (-luminance_distance / (sigma + 1e-10)).exp();
+                let w_normal = if guide_p.normal.near_zero() && guide_q.normal.near_zero() {
+                    1.0
+                } else {
+                    guide_p
+                        .normal
+                        .dot(guide_q.normal)
+                        .max(0.0)
+                        .powf(self.sigma_normal)
+                };
+                let albedo_distance = (guide_p.albedo - guide_q.albedo).length_squared();
+                let w_albedo = (-albedo_distance / (self.sigma_albedo * self.sigma_albedo)).exp();
+
+                let weight = hx * hy * w_luminance * w_normal * w_albedo;
+                colour_sum += illumination[q] * weight;
+                weight_sum += weight;
+                variance_sum += weight * weight * variance[q];
+            }
+        }
+
+        // The centre tap always has a positive weight, so the sums cannot be zero.
+        (
+            colour_sum / weight_sum,
+            variance_sum / (weight_sum * weight_sum),
+        )
+    }
+}
+
+// A 3x3 Gaussian over the variance, which SVGF uses to steady the luminance weights.
+fn blurred_variance(x: usize, y: usize, width: usize, height: usize, variance: &[f64]) -> f64 {
+    const GAUSSIAN: [f64; 3] = [0.25, 0.5, 0.25];
+    let mut sum = 0.0;
+    let mut weight_sum = 0.0;
+    for (ky, gy) in GAUSSIAN.iter().enumerate() {
+        let Some(qy) = (y + ky).checked_sub(1).filter(|&qy| qy < height) else {
+            continue;
+        };
+        for (kx, gx) in GAUSSIAN.iter().enumerate() {
+            let Some(qx) = (x + kx).checked_sub(1).filter(|&qx| qx < width) else {
+                continue;
+            };
+            sum += gx * gy * variance[qy * width + qx];
+            weight_sum += gx * gy;
+        }
+    }
+    sum / weight_sum
+}
+
+// Albedo is floored so that black surfaces do not divide by zero.
+fn demodulation_factor(albedo: Colour) -> Colour {
+    Colour::new(
+        albedo.x().max(0.01),
+        albedo.y().max(0.01),
+        albedo.z().max(0.01),
+    )
+}
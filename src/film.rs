@@ -0,0 +1,137 @@
+use crate::*;
+
+// Splats are summed in 64.64 fixed point rather than floating point. Integer addition does not
+// depend on order, so a pixel that collects samples from several tiles or passes gets the same
+// bits however the work was split between threads.
+const FIXED_POINT_ONE: f64 = 18446744073709551616.0;
+
+// Smallest total filter weight a pixel is resolved from.
+const MIN_WEIGHT: f64 = 1e-3;
+
+fn to_fixed(x: f64) -> i128 {
+    (x * FIXED_POINT_ONE) as i128
+}
+
+fn from_fixed(x: i128) -> f64 {
+    x as f64 / FIXED_POINT_ONE
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilmPixel {
+    pub sum: [i128; 3],
+    pub weight: i128,
+}
+
+// Filter-weighted sums of the samples splatted over a rectangle of the image: either the whole
+// image, or a tile grown by the filter radius so its samples can reach the neighbouring pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Film {
+    pub x0: u32,
+    pub y0: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::region(0, 0, width, height)
+    }
+
+    pub fn region(x0: u32, y0: u32, width: u32, height: u32) -> Self {
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            pixels: vec![FilmPixel::default(); width as usize * height as usize],
+        }
+    }
+
+    // The part of an `image_width` x `image_height` image that samples taken in pixels
+    // [x0, x1) x [y0, y1) can reach through `filter`.
+    pub fn for_pixels(
+        filter: &dyn Filter,
+        (x0, y0, x1, y1): (u32, u32, u32, u32),
+        image_width: u32,
+        image_height: u32,
+    ) -> Self {
+        let margin = filter.radius().ceil() as u32;
+        let (x0, y0) = (x0.saturating_sub(margin), y0.saturating_sub(margin));
+        let x1 = (x1 + margin).min(image_width);
+        let y1 = (y1 + margin).min(image_height);
+        Self::region(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    // Adds a sample at (x, y), in pixels with pixel (i, j) centred on (i, j), to every pixel of
+    // the region within the filter's radius.
+    pub fn splat(&mut self, filter: &dyn Filter, x: f64, y: f64, colour: Colour) {
+        let radius = filter.radius();
+        let columns = self.span(x - radius, x + radius, self.x0, self.width);
+        let rows = self.span(y - radius, y + radius, self.y0, self.height);
+
+        for py in rows {
+            for px in columns.clone() {
+                let weight = filter.evaluate(x - px as f64, y - py as f64);
+                if weight == 0.0 {
+                    continue;
+                }
+                let index = (py - self.y0) as usize * self.width as usize + (px - self.x0) as usize;
+                let pixel = &mut self.pixels[index];
+                for c in 0..3 {
+                    pixel.sum[c] = pixel.sum[c].saturating_add(to_fixed(colour[c] * weight));
+                }
+                pixel.weight = pixel.weight.saturating_add(to_fixed(weight));
+            }
+        }
+    }
+
+    fn span(&self, min: f64, max: f64, start: u32, len: u32) -> std::ops::Range<u32> {
+        let first = min.ceil().max(start as f64) as u32;
+        let last = (max.floor() + 1.0).clamp(start as f64, (start + len) as f64) as u32;
+        first..last.max(first)
+    }
+
+    // Adds the sums of a region film into the matching pixels of this one.
+    pub fn merge(&mut self, other: &Film) {
+        for row in 0..other.height {
+            let y = other.y0 + row;
+            if y < self.y0 || y >= self.y0 + self.height {
+                continue;
+            }
+            for column in 0..other.width {
+                let x = other.x0 + column;
+                if x < self.x0 || x >= self.x0 + self.width {
+                    continue;
+                }
+                let from = &other.pixels[(row * other.width + column) as usize];
+                let to = &mut self.pixels[((y - self.y0) * self.width + (x - self.x0)) as usize];
+                for c in 0..3 {
+                    to.sum[c] = to.sum[c].saturating_add(from.sum[c]);
+                }
+                to.weight = to.weight.saturating_add(from.weight);
+            }
+        }
+    }
+
+    // The weighted average for each pixel, or None where the samples' total weight is too small
+    // to divide by. Filters with negative lobes can leave a sampled pixel's weight near zero.
+    pub fn resolve(&self) -> Vec<Option<Colour>> {
+        self.pixels
+            .iter()
+            .map(|pixel| {
+                let weight = from_fixed(pixel.weight);
+                if weight <= MIN_WEIGHT {
+                    return None;
+                }
+                Some(
+                    Colour::new(
+                        from_fixed(pixel.sum[0]),
+                        from_fixed(pixel.sum[1]),
+                        from_fixed(pixel.sum[2]),
+                    ) / weight,
+                )
+            })
+            .collect()
+    }
+}
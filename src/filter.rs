@@ -0,0 +1,204 @@
+use crate::*;
+
+// A pixel reconstruction filter: how much a sample at offset (x, y) from a pixel's centre, in
+// pixels, counts towards that pixel. Weights may be negative; the film normalises by their sum.
+pub trait Filter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn radius(&self) -> f64;
+
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+pub fn filter_from_name(name: &str, radius: Option<f64>) -> Option<Arc<dyn Filter>> {
+    match name.to_ascii_lowercase().as_str() {
+        "box" => Some(Arc::new(BoxFilter::new(radius.unwrap_or(0.5)))),
+        "tent" | "triangle" => Some(Arc::new(TentFilter::new(radius.unwrap_or(1.0)))),
+        "gaussian" => Some(Arc::new(GaussianFilter::new(radius.unwrap_or(1.5)))),
+        "mitchell" => Some(Arc::new(MitchellFilter::new(radius.unwrap_or(2.0)))),
+        "lanczos" => Some(Arc::new(LanczosFilter::new(radius.unwrap_or(3.0)))),
+        _ => None,
+    }
+}
+
+pub const FILTER_NAMES: [&str; 5] = ["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn name(&self) -> &'static str {
+        "box"
+    }
+
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn name(&self) -> &'static str {
+        "tent"
+    }
+
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+// A Gaussian with a standard deviation of a third of the radius, shifted down so it reaches
+// zero at the radius instead of stopping abruptly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64) -> Self {
+        Self {
+            radius,
+            sigma: radius / 3.0,
+        }
+    }
+
+    fn gaussian(&self, x: f64) -> f64 {
+        let g = |x: f64| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn name(&self) -> &'static str {
+        "gaussian"
+    }
+
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+// Mitchell and Netravali's cubic with B = C = 1/3, stretched so its support of two pixels covers
+// the radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MitchellFilter {
+    radius: f64,
+}
+
+impl MitchellFilter {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+
+    fn mitchell(&self, x: f64) -> f64 {
+        let (b, c) = (Self::B, Self::C);
+        let x = (2.0 * x / self.radius).abs();
+        if x >= 2.0 {
+            0.0
+        } else if x >= 1.0 {
+            ((-b - 6.0 * c) * x.powi(3)
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn name(&self) -> &'static str {
+        "mitchell"
+    }
+
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+// The sinc function windowed by a wider sinc that reaches zero at the radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+
+    fn lanczos(&self, x: f64) -> f64 {
+        let sinc = |x: f64| {
+            if x.abs() < 1e-5 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            }
+        };
+        if x.abs() >= self.radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn name(&self) -> &'static str {
+        "lanczos"
+    }
+
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
@@ -0,0 +1,209 @@
+use crate::*;
+use image::{Rgb, Rgb32FImage, RgbImage};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Colour>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Colour::zero(); width as usize * height as usize],
+        }
+    }
+
+    pub fn index(&self, i: u32, j: u32) -> usize {
+        j as usize * self.width as usize + i as usize
+    }
+
+    pub fn pixel(&self, i: u32, j: u32) -> Colour {
+        self.pixels[self.index(i, j)]
+    }
+
+    pub fn pixel_mut(&mut self, i: u32, j: u32) -> &mut Colour {
+        let index = self.index(i, j);
+        &mut self.pixels[index]
+    }
+
+    pub fn to_rgb_image(&self, display_transform: &DisplayTransform) -> RgbImage {
+        let mut imgbuf = RgbImage::new(self.width, self.height);
+        for (pixel, colour) in imgbuf.pixels_mut().zip(&self.pixels) {
+            display_transform.write_colour(pixel, *colour);
+        }
+        imgbuf
+    }
+
+    pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+        let mut imgbuf = Rgb32FImage::new(self.width, self.height);
+        for (pixel, colour) in imgbuf.pixels_mut().zip(&self.pixels) {
+            *pixel = Rgb([colour.x() as f32, colour.y() as f32, colour.z() as f32]);
+        }
+        imgbuf
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelSamples {
+    pub sum: Colour,
+    pub sum_sq: f64,
+    pub count: u32,
+    // Only allocated when the camera records AOVs.
+    pub aov: Option<Box<AovPixel>>,
+}
+
+impl PixelSamples {
+    pub fn new() -> Self {
+        Self {
+            sum: Colour::zero(),
+            sum_sq: 0.0,
+            count: 0,
+            aov: None,
+        }
+    }
+
+    pub fn add(&mut self, colour: Colour) {
+        self.sum += colour;
+        self.sum_sq += luminance(colour).powi(2);
+        self.count += 1;
+    }
+
+    pub fn mean(&self) -> Colour {
+        if self.count == 0 {
+            Colour::zero()
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    // Estimated variance of the mean luminance; infinite until there are two samples.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.count as f64;
+        let mean = luminance(self.sum) / n;
+        ((self.sum_sq - n * mean * mean) / (n - 1.0)).max(0.0) / n
+    }
+
+    // Standard error of the mean luminance relative to the mean itself. The mean is floored so
+    // that black pixels converge rather than dividing by zero.
+    pub fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let mean = luminance(self.sum) / self.count as f64;
+        self.variance().sqrt() / mean.max(1e-3)
+    }
+}
+
+impl Default for PixelSamples {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<PixelSamples>,
+    // Filter-weighted sums when the camera has a reconstruction filter.
+    pub film: Option<Film>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![PixelSamples::new(); width as usize * height as usize],
+            film: None,
+        }
+    }
+
+    pub fn min_samples(&self) -> u32 {
+        self.pixels.iter().map(|p| p.count).min().unwrap_or(0)
+    }
+
+    pub fn max_samples(&self) -> u32 {
+        self.pixels.iter().map(|p| p.count).max().unwrap_or(0)
+    }
+
+    pub fn mean_samples(&self) -> f64 {
+        if self.pixels.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.pixels.iter().map(|p| p.count as u64).sum();
+        total as f64 / self.pixels.len() as f64
+    }
+
+    // The achieved sample count as written to output metadata: a single number when every pixel
+    // has the same count, otherwise the range and mean.
+    pub fn samples_summary(&self) -> String {
+        let (min, max) = (self.min_samples(), self.max_samples());
+        if min == max {
+            min.to_string()
+        } else {
+            format!("{}-{} (mean {:.1})", min, max, self.mean_samples())
+        }
+    }
+
+    pub fn mean_relative_error(&self) -> f64 {
+        if self.pixels.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self.pixels.iter().map(PixelSamples::relative_error).sum();
+        total / self.pixels.len() as f64
+    }
+
+    pub fn resolve(&self) -> Framebuffer {
+        let pixels = match &self.film {
+            // Pixels the film cannot resolve fall back to their own unfiltered mean.
+            Some(film) => film
+                .resolve()
+                .into_iter()
+                .zip(&self.pixels)
+                .map(|(colour, pixel)| colour.unwrap_or_else(|| pixel.mean()))
+                .collect(),
+            None => self.pixels.iter().map(PixelSamples::mean).collect(),
+        };
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    pub fn resolve_aov(&self, aov: Aov) -> Framebuffer {
+        let empty = AovPixel::new();
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|p| p.aov.as_deref().unwrap_or(&empty).value(aov))
+                .collect(),
+        }
+    }
+
+    // Colours each pixel by its sample count relative to `max_samples`.
+    pub fn sample_heatmap(&self, max_samples: u32) -> Framebuffer {
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|p| heat_colour(p.count as f64 / max_samples.max(1) as f64))
+            .collect();
+
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::*;
+use gltf::camera::Projection;
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use gltf::texture::WrappingMode;
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, RgbImage, RgbaImage};
+use std::collections::HashMap;
+use std::path::Path;
+
+// Loads the default scene of a glTF 2.0 file: a .gltf with its buffers and images alongside it
+// or embedded as data URIs, or a self-contained .glb. Each mesh primitive is baked into world
+// space through its node's transform and those of the node's ancestors, becoming a triangle
+// mesh, and the perspective cameras are returned in the order the scene reaches them.
+pub fn load_gltf(path: &Path) -> Result<Model, ModelError> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(|err| match err {
+        gltf::Error::Io(err) => ModelError::Io(path.to_path_buf(), err),
+        err => ModelError::Invalid(path.to_path_buf(), err.to_string()),
+    })?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let buffers = gltf::import_buffers(&document, Some(base), blob)
+        .map_err(|err| ModelError::Invalid(path.to_path_buf(), format!("buffers: {}", err)))?;
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| ModelError::Invalid(path.to_path_buf(), "file has no scene".to_string()))?;
+
+    let mut loader = GltfLoader {
+        path,
+        base,
+        buffers,
+        materials: HashMap::new(),
+        images: HashMap::new(),
+        model: Model::new(),
+    };
+    for node in scene.nodes() {
+        loader.node(&node, &Mat4::identity())?;
+    }
+    Ok(loader.model)
+}
+
+struct GltfLoader<'a> {
+    path: &'a Path,
+    base: &'a Path,
+    buffers: Vec<gltf::buffer::Data>,
+    // Keyed by glTF material index, with None for primitives that name no material.
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    // Decoded images by glTF image index, shared by every texture that samples them.
+    images: HashMap<usize, Arc<ImageTexture>>,
+    model: Model,
+}
+
+impl GltfLoader<'_> {
+    fn invalid(&self, message: String) -> ModelError {
+        ModelError::Invalid(self.path.to_path_buf(), message)
+    }
+
+    fn node(&mut self, node: &gltf::Node, parent: &Mat4) -> Result<(), ModelError> {
+        let local = Mat4::from_columns(
+            node.transform()
+                .matrix()
+                .map(|column| column.map(f64::from)),
+        );
+        let world = *parent * local;
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.primitive(mesh.index(), &primitive, &world)?;
+            }
+        }
+        // Orthographic cameras are skipped, as the renderer's camera is a pinhole.
+        if let Some(camera) = node.camera()
+            && let Projection::Perspective(perspective) = camera.projection()
+        {
+            let look_from = world.transform_point(Point3::zero());
+            let forward = world.transform_vector(Vec3::new(0.0, 0.0, -1.0));
+            self.model.cameras.push(ModelCamera {
+                look_from,
+                look_at: look_from + forward.unit_vector(),
+                vup: world.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+                vfov: f64::from(perspective.yfov()).to_degrees(),
+                aspect_ratio: perspective.aspect_ratio().map(f64::from),
+            });
+        }
+        for child in node.children() {
+            self.node(&child, &world)?;
+        }
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        mesh: usize,
+        primitive: &gltf::Primitive,
+        world: &Mat4,
+    ) -> Result<(), ModelError> {
+        // Normals go through the inverse transpose; a node scaled flat has none to keep.
+        let normal_matrix = world.inverse().map(|inverse| inverse.transpose());
+        let material = primitive.material();
+        let uv_set = material
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map_or(0, |info| info.tex_coord());
+
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions: Vec<Point3> = reader
+            .read_positions()
+            .ok_or_else(|| {
+                self.invalid(format!("mesh {} has a primitive without positions", mesh))
+            })?
+            .map(|[x, y, z]| world.transform_point(vec3_from(x, y, z)))
+            .collect();
+        let normals: Option<Vec<Vec3>> = match (reader.read_normals(), normal_matrix) {
+            (Some(normals), Some(normal_matrix)) => Some(
+                normals
+                    .map(|[x, y, z]| {
+                        let normal = vec3_from(x, y, z);
+                        normal_matrix.transform_vector(normal).unit_vector()
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        // glTF puts the texture origin at the top left, the renderer at the bottom left.
+        let uvs: Option<Vec<(f64, f64)>> = reader.read_tex_coords(uv_set).map(|uvs| {
+            uvs.into_f32()
+                .map(|[u, v]| (f64::from(u), 1.0 - f64::from(v)))
+                .collect()
+        });
+        let colours: Option<Vec<Colour>> = reader.read_colors(0).map(|colours| {
+            colours
+                .into_rgb_f32()
+                .map(|[r, g, b]| vec3_from(r, g, b))
+                .collect()
+        });
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let mut faces: Vec<[usize; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|face| [face[0], face[1], face[2]])
+                .collect(),
+            // Every other strip triangle is wound the other way round.
+            Mode::TriangleStrip => (2..indices.len())
+                .map(|i| match i % 2 {
+                    0 => [indices[i - 2], indices[i - 1], indices[i]],
+                    _ => [indices[i - 1], indices[i - 2], indices[i]],
+                })
+                .collect(),
+            Mode::TriangleFan => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            // Points and lines have no surface to hit.
+            _ => return Ok(()),
+        };
+        // A mirroring transform turns the winding, and with it the facing, inside out.
+        if world.determinant() < 0.0 {
+            faces.iter_mut().for_each(|face| face.swap(1, 2));
+        }
+
+        let material = self.material(&material)?;
+        let mesh = TriangleMesh::new(positions, &faces, normals, uvs, colours, material)
+            .map_err(|message| self.invalid(format!("mesh {}: {}", mesh, message)))?;
+        self.model.add_mesh(mesh);
+        Ok(())
+    }
+
+    // Emissive materials become lights, mostly transmissive ones glass, mostly metallic ones
+    // metal (as rough as their roughness), and the rest diffuse. Metallic-roughness, normal and
+    // occlusion textures are ignored.
+    fn material(&mut self, material: &gltf::Material) -> Result<Arc<dyn Material>, ModelError> {
+        if let Some(existing) = self.materials.get(&material.index()) {
+            return Ok(existing.clone());
+        }
+        let converted: Arc<dyn Material> = match material.index() {
+            None => default_material(),
+            Some(_) => {
+                let pbr = material.pbr_metallic_roughness();
+                let [r, g, b, _] = pbr.base_color_factor();
+                let base_colour = vec3_from(r, g, b);
+                let [r, g, b] = material.emissive_factor();
+                let emission =
+                    vec3_from(r, g, b) * f64::from(material.emissive_strength().unwrap_or(1.0));
+                let roughness = f64::from(pbr.roughness_factor());
+
+                if emission.x().max(emission.y()).max(emission.z()) > 0.0 {
+                    match material.emissive_texture() {
+                        Some(info) => Arc::new(DiffuseLight::new(self.texture(&info, emission)?)),
+                        None => Arc::new(DiffuseLight::from_colour(emission)),
+                    }
+                } else if material
+                    .transmission()
+                    .is_some_and(|transmission| transmission.transmission_factor() > 0.5)
+                {
+                    Arc::new(Dielectric::new(f64::from(material.ior().unwrap_or(1.5))))
+                } else if pbr.metallic_factor() >= 0.5 {
+                    Arc::new(Metal::new(base_colour, roughness * roughness))
+                } else {
+                    match pbr.base_color_texture() {
+                        Some(info) => Arc::new(Lambertian::new(self.texture(&info, base_colour)?)),
+                        None => Arc::new(Lambertian::from_colour(base_colour)),
+                    }
+                }
+            }
+        };
+        self.materials.insert(material.index(), converted.clone());
+        Ok(converted)
+    }
+
+    fn texture(
+        &mut self,
+        info: &gltf::texture::Info,
+        factor: Colour,
+    ) -> Result<Arc<dyn Texture>, ModelError> {
+        let texture = info.texture();
+        let source = texture.source();
+        let image = match self.images.get(&source.index()) {
+            Some(image) => image.clone(),
+            None => {
+                let data =
+                    gltf::image::Data::from_source(source.source(), Some(self.base), &self.buffers)
+                        .map_err(|err| {
+                            self.invalid(format!("image {}: {}", source.index(), err))
+                        })?;
+                let decoded = decoded_image(data).ok_or_else(|| {
+                    self.invalid(format!(
+                        "image {} has an unsupported pixel format",
+                        source.index()
+                    ))
+                })?;
+                let image = Arc::new(ImageTexture::new(RtwImage::from_image(&decoded)));
+                self.images.insert(source.index(), image.clone());
+                image
+            }
+        };
+        let sampler = texture.sampler();
+        Ok(Arc::new(GltfTexture {
+            image,
+            factor,
+            wrap: [sampler.wrap_s(), sampler.wrap_t()],
+        }))
+    }
+}
+
+// An image scaled by a material's colour factor and wrapped the way its sampler asks.
+struct GltfTexture {
+    image: Arc<ImageTexture>,
+    factor: Colour,
+    wrap: [WrappingMode; 2],
+}
+
+impl Texture for GltfTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Colour {
+        let [u, v] = [(u, self.wrap[0]), (v, self.wrap[1])].map(|(x, wrap)| match wrap {
+            WrappingMode::ClampToEdge => x,
+            WrappingMode::Repeat => x.rem_euclid(1.0),
+            WrappingMode::MirroredRepeat => 1.0 - (x.rem_euclid(2.0) - 1.0).abs(),
+        });
+        self.image.value(u, v, p) * self.factor
+    }
+}
+
+// glTF only embeds PNG and JPEG images, so 8 and 16 bits per channel cover them.
+fn decoded_image(data: gltf::image::Data) -> Option<DynamicImage> {
+    let (width, height) = (data.width, data.height);
+    let wide = |bytes: &[u8]| -> Vec<u16> {
+        bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]))
+            .collect()
+    };
+    match data.format {
+        Format::R8 => GrayImage::from_raw(width, height, data.pixels).map(DynamicImage::from),
+        Format::R8G8 => {
+            GrayAlphaImage::from_raw(width, height, data.pixels).map(DynamicImage::from)
+        }
+        Format::R8G8B8 => RgbImage::from_raw(width, height, data.pixels).map(DynamicImage::from),
+        Format::R8G8B8A8 => RgbaImage::from_raw(width, height, data.pixels).map(DynamicImage::from),
+        Format::R16 => {
+            ImageBuffer::from_raw(width, height, wide(&data.pixels)).map(DynamicImage::ImageLuma16)
+        }
+        Format::R16G16 => {
+            ImageBuffer::from_raw(width, height, wide(&data.pixels)).map(DynamicImage::ImageLumaA16)
+        }
+        Format::R16G16B16 => {
+            ImageBuffer::from_raw(width, height, wide(&data.pixels)).map(DynamicImage::ImageRgb16)
+        }
+        Format::R16G16B16A16 => {
+            ImageBuffer::from_raw(width, height, wide(&data.pixels)).map(DynamicImage::ImageRgba16)
+        }
+        _ => None,
+    }
+}
+
+fn vec3_from(x: f32, y: f32, z: f32) -> Vec3 {
+    Vec3::new(f64::from(x), f64::from(y), f64::from(z))
+}
@@ -1,4 +1,13 @@
 use crate::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Object IDs are handed out in construction order starting from 1, so a scene built the same way
+// gets the same IDs every run. 0 is left for rays that hit nothing.
+static NEXT_OBJECT_ID: AtomicU32 = AtomicU32::new(1);
+
+pub fn next_object_id() -> u32 {
+    NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Clone)]
 pub struct HitRecord {
@@ -9,6 +18,10 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    pub object_id: u32,
+    // Colour painted on the mesh vertices around the hit, which tints the material; white where
+    // there is none.
+    pub vertex_colour: Colour,
 }
 
 impl HitRecord {
@@ -21,6 +34,8 @@ impl HitRecord {
             u: 0.0,
             v: 0.0,
             front_face: false,
+            object_id: 0,
+            vertex_colour: Colour::one(),
         }
     }
 
@@ -41,8 +56,27 @@ impl Default for HitRecord {
 }
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+    // `sampler` supplies the random numbers a hit may need, such as where a ray scatters inside
+    // a medium. Surfaces draw none.
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord, sampler: &mut dyn Sampler)
+    -> bool;
     fn bounding_box(&self) -> AABB;
+
+    // Density, with respect to solid angle seen from `origin`, of `random` choosing `direction`.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // A direction from `origin` towards a random point on the object.
+    fn random(&self, _origin: &Point3, _sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // BVH nodes that `hit` would test the ray against, for the traversal cost view. Kept apart
+    // from `hit` so ordinary rendering does not pay for the count.
+    fn bvh_nodes_visited(&self, _r: &Ray, _ray_t: Interval, _sampler: &mut dyn Sampler) -> u64 {
+        0
+    }
 }
 
 pub struct Translate {
@@ -60,13 +94,21 @@ impl Translate {
             bbox,
         }
     }
+
+    fn offset_ray(&self, r: &Ray) -> Ray {
+        Ray::new(r.origin() - self.offset, r.direction(), r.time())
+    }
 }
 
 impl Hittable for Translate {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let offset_r = Ray::new(r.origin() - self.offset, r.direction(), r.time());
-
-        if !self.object.hit(&offset_r, ray_t, rec) {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if !self.object.hit(&self.offset_ray(r), ray_t, rec, sampler) {
             return false;
         }
 
@@ -78,6 +120,11 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn bvh_nodes_visited(&self, r: &Ray, ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        self.object
+            .bvh_nodes_visited(&self.offset_ray(r), ray_t, sampler)
+    }
 }
 
 pub struct RotateY {
@@ -126,10 +173,8 @@ impl RotateY {
             bbox,
         }
     }
-}
 
-impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn rotated_ray(&self, r: &Ray) -> Ray {
         let origin = Point3::new(
             self.cos_theta * r.origin().x() - self.sin_theta * r.origin().z(),
             r.origin().y(),
@@ -142,9 +187,19 @@ impl Hittable for RotateY {
             self.sin_theta * r.direction().x() + self.cos_theta * r.direction().z(),
         );
 
-        let rotated_r = Ray::new(origin, direction, r.time());
+        Ray::new(origin, direction, r.time())
+    }
+}
 
-        if !self.object.hit(&rotated_r, ray_t, rec) {
+impl Hittable for RotateY {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if !self.object.hit(&self.rotated_ray(r), ray_t, rec, sampler) {
             return false;
         }
 
@@ -166,4 +221,99 @@ impl Hittable for RotateY {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn bvh_nodes_visited(&self, r: &Ray, ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        self.object
+            .bvh_nodes_visited(&self.rotated_ray(r), ray_t, sampler)
+    }
+}
+
+// Places an object with any affine transform, so it can also be rotated about other axes,
+// scaled, sheared or mirrored. Rays are carried into the object's space by the inverse without
+// renormalising, which leaves hit distances unchanged.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    forward: Mat4,
+    inverse: Mat4,
+    // The inverse transpose, which keeps normals perpendicular to the surface, and on the same
+    // side of it as the ray, so front_face still holds.
+    normal_matrix: Mat4,
+    // Volume scale of the transform, which light sampling densities need.
+    determinant: f64,
+    bbox: AABB,
+}
+
+impl Transform {
+    // Panics if `forward` is singular, which would flatten the object.
+    pub fn new(object: Arc<dyn Hittable>, forward: Mat4) -> Self {
+        let inverse = forward.inverse().expect("a transform must not be singular");
+        let bbox = forward.transform_box(&object.bounding_box());
+        Self {
+            object,
+            forward,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            determinant: forward.determinant(),
+            bbox,
+        }
+    }
+
+    fn object_ray(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(r.origin()),
+            self.inverse.transform_vector(r.direction()),
+            r.time(),
+        )
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if !self.object.hit(&self.object_ray(r), ray_t, rec, sampler) {
+            return false;
+        }
+
+        rec.p = self.forward.transform_point(rec.p);
+        rec.normal = self
+            .normal_matrix
+            .transform_vector(rec.normal)
+            .unit_vector();
+
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let object_direction = self.inverse.transform_vector(*direction);
+        let pdf = self
+            .object
+            .pdf_value(&self.inverse.transform_point(*origin), &object_direction);
+
+        // The transform maps a unit direction w to a solid angle larger by |det| / |Fw|^3.
+        let stretch = self
+            .forward
+            .transform_vector(object_direction.unit_vector())
+            .length();
+        pdf * stretch.powi(3) / self.determinant.abs()
+    }
+
+    fn bvh_nodes_visited(&self, r: &Ray, ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        self.object
+            .bvh_nodes_visited(&self.object_ray(r), ray_t, sampler)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let object_origin = self.inverse.transform_point(*origin);
+        self.forward
+            .transform_vector(self.object.random(&object_origin, sampler))
+    }
 }
@@ -36,13 +36,24 @@ impl Default for HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         let mut temp_rec = HitRecord::new();
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
         for object in self.objects.iter() {
-            if object.hit(ray, Interval::new(ray_t.min, closest_so_far), &mut temp_rec) {
+            if object.hit(
+                ray,
+                Interval::new(ray_t.min, closest_so_far),
+                &mut temp_rec,
+                sampler,
+            ) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
                 *rec = temp_rec.clone();
@@ -55,4 +66,41 @@ impl Hittable for HittableList {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // Later objects are tested against a ray shortened to the closest hit so far, as in `hit`.
+    fn bvh_nodes_visited(&self, ray: &Ray, ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        let mut rec = HitRecord::new();
+        let mut closest_so_far = ray_t.max;
+        let mut nodes = 0;
+
+        for object in self.objects.iter() {
+            let object_t = Interval::new(ray_t.min, closest_so_far);
+            nodes += object.bvh_nodes_visited(ray, object_t, sampler);
+            if object.hit(ray, object_t, &mut rec, sampler) {
+                closest_so_far = rec.t;
+            }
+        }
+
+        nodes
+    }
+
+    // Picks one object uniformly, so the density is the average of the objects' densities.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = (sampler.get_1d() * self.objects.len() as f64) as usize;
+        self.objects[index.min(self.objects.len() - 1)].random(origin, sampler)
+    }
 }
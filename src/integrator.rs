@@ -0,0 +1,587 @@
+use crate::*;
+
+// Everything an integrator needs to know about the scene besides the ray itself.
+pub struct Scene<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: Option<&'a dyn Hittable>,
+    pub background: Colour,
+    pub max_depth: u32,
+}
+
+impl Scene<'_> {
+    // Length of the world's bounding box diagonal, which the debug views scale distances by.
+    fn extent(&self) -> f64 {
+        let bbox = self.world.bounding_box();
+        let size = Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size());
+        if size.length().is_finite() {
+            size.length()
+        } else {
+            1.0
+        }
+    }
+
+    fn farthest_corner(&self, origin: &Point3) -> f64 {
+        let bbox = self.world.bounding_box();
+        let corner =
+            |interval: Interval, x: f64| (interval.min - x).abs().max((interval.max - x).abs());
+        let distance = Vec3::new(
+            corner(bbox.x, origin.x()),
+            corner(bbox.y, origin.y()),
+            corner(bbox.z, origin.z()),
+        )
+        .length();
+        if distance.is_finite() && distance > 0.0 {
+            distance
+        } else {
+            1.0
+        }
+    }
+
+    fn first_hit(&self, ray: &Ray, sampler: &mut dyn Sampler, rays: &mut u64) -> Option<HitRecord> {
+        *rays += 1;
+        let mut rec = HitRecord::new();
+        self.world
+            .hit(ray, Interval::new(0.001, f64::INFINITY), &mut rec, sampler)
+            .then_some(rec)
+    }
+}
+
+// Turns a camera ray into the colour recorded for it. `rays` counts every ray cast against the
+// scene, for the progress display.
+pub trait Integrator: Send + Sync {
+    // The name `integrator_from_name` knows it by.
+    fn name(&self) -> &'static str;
+
+    // The name followed by any settings that change the image, for checkpoints to compare.
+    fn description(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour;
+
+    // `radiance`, also filling in the AOVs for the sample. Integrators that do not trace light
+    // paths leave the albedo black and count everything they return as direct light.
+    fn radiance_with_aovs(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+        aov: &mut AovSample,
+    ) -> Colour {
+        if let Some(rec) = scene.first_hit(ray, sampler, rays) {
+            aov.record_hit(ray, &rec);
+        }
+        let radiance = self.radiance(ray, scene, sampler, rays);
+        aov.direct = radiance;
+        radiance
+    }
+}
+
+pub fn integrator_from_name(name: &str) -> Option<Arc<dyn Integrator>> {
+    match name.to_ascii_lowercase().as_str() {
+        "path" => Some(Arc::new(PathIntegrator::default())),
+        "normals" => Some(Arc::new(NormalsIntegrator)),
+        "depth" => Some(Arc::new(DepthIntegrator::default())),
+        "uv" => Some(Arc::new(UvIntegrator)),
+        "material-id" => Some(Arc::new(MaterialIdIntegrator)),
+        "bvh-cost" => Some(Arc::new(BvhCostIntegrator::default())),
+        "ao" => Some(Arc::new(AmbientOcclusionIntegrator::default())),
+        _ => None,
+    }
+}
+
+pub const INTEGRATOR_NAMES: [&str; 7] = [
+    "path",
+    "normals",
+    "depth",
+    "uv",
+    "material-id",
+    "bvh-cost",
+    "ao",
+];
+
+// Bounces every path takes before Russian roulette may end it.
+const ROULETTE_START_BOUNCE: u32 = 3;
+
+// Unbiased by default. The clamps and regularisation trade a little energy and sharpness for
+// far fewer fireflies from rare, bright paths such as caustics through glass and metal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathIntegrator {
+    // Largest value any channel of one camera sample may reach; brighter samples are scaled
+    // down whole so they keep their hue.
+    pub max_sample_radiance: Option<f64>,
+    // The same limit applied to each contribution a path picks up after its first hit, leaving
+    // directly visible emitters and direct lighting of the first surface alone.
+    pub max_bounce_radiance: Option<f64>,
+    // Roughness in (0, 1] that specular bounces take on once the path has scattered diffusely,
+    // turning them into glossy lobes that light sampling can reach.
+    pub regularize: Option<f64>,
+}
+
+// How a non-specular bounce scatters: the material's own densities, or for a regularised
+// specular bounce a glossy lobe that serves as both its sampling and scattering density.
+struct Lobe<'a> {
+    attenuation: Colour,
+    pdf: &'a dyn Pdf,
+    regularized: bool,
+}
+
+impl Lobe<'_> {
+    fn scattering_pdf(&self, ray: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.regularized {
+            self.pdf.value(&scattered.direction())
+        } else {
+            rec.material.scattering_pdf(ray, rec, scattered)
+        }
+    }
+}
+
+impl PathIntegrator {
+    // Next-event estimation: one shadow ray towards a random point on the lights, weighted by
+    // the power heuristic against the material's own density for the same direction. Light that
+    // BSDF sampling finds is weighted the same way in `radiance`, so nothing is counted twice.
+    fn sample_lights(
+        ray: &Ray,
+        rec: &HitRecord,
+        lobe: &Lobe<'_>,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        let Some(lights) = scene.lights else {
+            return Colour::zero();
+        };
+
+        let direction = lights.random(&rec.p, sampler);
+        let light_pdf = lights.pdf_value(&rec.p, &direction);
+        if light_pdf <= 0.0 {
+            return Colour::zero();
+        }
+
+        let shadow_ray = Ray::new(rec.p, direction, ray.time());
+        let scattering_pdf = lobe.scattering_pdf(ray, rec, &shadow_ray);
+        if scattering_pdf <= 0.0 {
+            return Colour::zero();
+        }
+
+        let Some(light_rec) = scene.first_hit(&shadow_ray, sampler, rays) else {
+            return Colour::zero();
+        };
+
+        let emitted = light_rec
+            .material
+            .emitted(light_rec.u, light_rec.v, &light_rec.p);
+        let weight = power_heuristic(light_pdf, lobe.pdf.value(&direction));
+        lobe.attenuation * emitted * scattering_pdf * weight / light_pdf
+    }
+
+    // Adds light the path picked up at `bounce`, clamped if it came after the first hit.
+    fn add_light(&self, light: &mut Colour, contribution: Colour, bounce: u32) {
+        *light += match self.max_bounce_radiance {
+            Some(max) if bounce > 0 => clamp_radiance(contribution, max),
+            _ => contribution,
+        };
+    }
+}
+
+impl PathIntegrator {
+    // Follows one path, carrying the product of the attenuations and sampling weights so far in
+    // `throughput`. Paths end when they escape, get absorbed, or lose at Russian roulette;
+    // `max_depth` only caps the rare path that keeps winning.
+    //
+    // Light that reaches the camera after at most one diffuse bounce, through any number of
+    // specular ones, counts as direct; the rest is indirect. The albedo is that of the first
+    // diffuse surface, tinted by the specular bounces on the way there.
+    fn trace(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+        mut aov: Option<&mut AovSample>,
+    ) -> Colour {
+        let mut ray = *ray;
+        let mut direct = Colour::zero();
+        let mut indirect = Colour::zero();
+        let mut throughput = Colour::one();
+        let mut specular_tint = Colour::one();
+        let mut albedo = None;
+        let mut diffuse_bounces = 0;
+        // The density the previous bounce chose `ray` with; None for camera rays and specular
+        // bounces, which light sampling cannot produce.
+        let mut scatter_pdf = None;
+
+        for bounce in 0..scene.max_depth {
+            let Some(rec) = scene.first_hit(&ray, sampler, rays) else {
+                let light = if diffuse_bounces <= 1 {
+                    &mut direct
+                } else {
+                    &mut indirect
+                };
+                self.add_light(light, throughput * scene.background, bounce);
+                albedo.get_or_insert(specular_tint * clamp_albedo(scene.background));
+                break;
+            };
+            if bounce == 0
+                && let Some(aov) = aov.as_deref_mut()
+            {
+                aov.record_hit(&ray, &rec);
+            }
+
+            let mut colour_from_emission = rec.material.emitted(rec.u, rec.v, &rec.p);
+            if let (Some(scatter_pdf), Some(lights)) = (scatter_pdf, scene.lights)
+                && colour_from_emission != Colour::zero()
+            {
+                let light_pdf = lights.pdf_value(&ray.origin(), &ray.direction());
+                colour_from_emission *= power_heuristic(scatter_pdf, light_pdf);
+            }
+            let light = if diffuse_bounces <= 1 {
+                &mut direct
+            } else {
+                &mut indirect
+            };
+            self.add_light(light, throughput * colour_from_emission, bounce);
+
+            let mut srec = ScatterRecord::new();
+            if !rec.material.scatter(&ray, &rec, &mut srec, sampler) {
+                albedo.get_or_insert(specular_tint * clamp_albedo(colour_from_emission));
+                break;
+            }
+
+            let glossy = match (&srec.pdf, self.regularize) {
+                (None, Some(roughness)) if diffuse_bounces > 0 => Some(PhongPdf::new(
+                    srec.skip_pdf_ray.direction(),
+                    phong_exponent(roughness),
+                )),
+                _ => None,
+            };
+            let lobe = match (&srec.pdf, &glossy) {
+                (Some(pdf), _) => Some(Lobe {
+                    attenuation: srec.attenuation,
+                    pdf: pdf.as_ref(),
+                    regularized: false,
+                }),
+                (None, Some(glossy)) => Some(Lobe {
+                    attenuation: srec.attenuation,
+                    pdf: glossy,
+                    regularized: true,
+                }),
+                (None, None) => None,
+            };
+
+            match lobe {
+                None => {
+                    throughput *= srec.attenuation;
+                    specular_tint *= srec.attenuation;
+                    ray = srec.skip_pdf_ray;
+                    scatter_pdf = None;
+                }
+                Some(lobe) => {
+                    albedo.get_or_insert(specular_tint * lobe.attenuation);
+                    let light = if diffuse_bounces == 0 {
+                        &mut direct
+                    } else {
+                        &mut indirect
+                    };
+                    let light_sample = Self::sample_lights(&ray, &rec, &lobe, scene, sampler, rays);
+                    self.add_light(light, throughput * light_sample, bounce);
+                    diffuse_bounces += 1;
+
+                    let scattered = Ray::new(rec.p, lobe.pdf.generate(sampler), ray.time());
+                    let pdf_value = lobe.pdf.value(&scattered.direction());
+                    if pdf_value <= 0.0 {
+                        break;
+                    }
+                    let scattering_pdf = lobe.scattering_pdf(&ray, &rec, &scattered);
+
+                    throughput *= lobe.attenuation * scattering_pdf / pdf_value;
+                    ray = scattered;
+                    scatter_pdf = Some(pdf_value);
+                }
+            }
+
+            // Past the first few bounces, continue with probability equal to the brightest
+            // throughput channel and scale up survivors so the estimate stays unbiased.
+            if bounce + 1 >= ROULETTE_START_BOUNCE {
+                let survival = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(1.0);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        if let Some(max) = self.max_sample_radiance {
+            let peak = max_channel(direct + indirect);
+            if peak > max {
+                direct *= max / peak;
+                indirect *= max / peak;
+            }
+        }
+
+        if let Some(aov) = aov {
+            aov.albedo = albedo.unwrap_or(Colour::zero());
+            aov.direct = direct;
+            aov.indirect = indirect;
+        }
+        direct + indirect
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn name(&self) -> &'static str {
+        "path"
+    }
+
+    // Settings are named after the command-line flags that set them.
+    fn description(&self) -> String {
+        let mut description = self.name().to_string();
+        for (flag, value) in [
+            ("clamp", self.max_sample_radiance),
+            ("clamp-bounce", self.max_bounce_radiance),
+            ("regularize", self.regularize),
+        ] {
+            if let Some(value) = value {
+                description += &format!(" {} {}", flag, value);
+            }
+        }
+        description
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        self.trace(ray, scene, sampler, rays, None)
+    }
+
+    fn radiance_with_aovs(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+        aov: &mut AovSample,
+    ) -> Colour {
+        self.trace(ray, scene, sampler, rays, Some(aov))
+    }
+}
+
+// Scales `colour` down, keeping its hue, so that no channel exceeds `max`.
+fn clamp_radiance(colour: Colour, max: f64) -> Colour {
+    let peak = max_channel(colour);
+    if peak > max {
+        colour * (max / peak)
+    } else {
+        colour
+    }
+}
+
+fn max_channel(colour: Colour) -> f64 {
+    colour.x().max(colour.y()).max(colour.z())
+}
+
+// Phong exponent of a glossy lobe with the given roughness, following the usual Beckmann
+// correspondence: roughness 1 spreads over the whole hemisphere.
+fn phong_exponent(roughness: f64) -> f64 {
+    (2.0 / (roughness * roughness) - 2.0).max(0.0)
+}
+
+// Emitters and the background stand in for an albedo with their colour, clipped to 1.
+fn clamp_albedo(colour: Colour) -> Colour {
+    Colour::new(
+        colour.x().min(1.0),
+        colour.y().min(1.0),
+        colour.z().min(1.0),
+    )
+}
+
+// Shading normal of the first hit, mapped from [-1, 1] to [0, 1] per axis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn name(&self) -> &'static str {
+        "normals"
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        scene
+            .first_hit(ray, sampler, rays)
+            .map_or(Colour::zero(), |rec| (rec.normal + Colour::one()) * 0.5)
+    }
+}
+
+// Distance along the ray to the first hit as a grey level reaching white at `far`, which
+// defaults to the farthest corner of the world's bounding box. Misses are white.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthIntegrator {
+    pub far: Option<f64>,
+}
+
+impl Integrator for DepthIntegrator {
+    fn name(&self) -> &'static str {
+        "depth"
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        let far = self
+            .far
+            .unwrap_or_else(|| scene.farthest_corner(&ray.origin()));
+        let distance = scene
+            .first_hit(ray, sampler, rays)
+            .map_or(far, |rec| rec.t * ray.direction().length());
+        Colour::one() * (distance / far).min(1.0)
+    }
+}
+
+// Texture coordinates of the first hit in the red and green channels.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn name(&self) -> &'static str {
+        "uv"
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        scene
+            .first_hit(ray, sampler, rays)
+            .map_or(Colour::zero(), |rec| Colour::new(rec.u, rec.v, 0.0))
+    }
+}
+
+// A flat colour per material instance. Materials have no names, so the colour comes from the
+// material's address: stable within a render, but not from one run to the next.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaterialIdIntegrator;
+
+impl Integrator for MaterialIdIntegrator {
+    fn name(&self) -> &'static str {
+        "material-id"
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        scene
+            .first_hit(ray, sampler, rays)
+            .map_or(Colour::zero(), |rec| {
+                let address = Arc::as_ptr(&rec.material) as *const () as usize;
+                let hash = Rng::hash(&[address as u64]);
+                let channel = |shift: u32| srgb_to_linear(((hash >> shift) & 0xff) as f64 / 255.0);
+                Colour::new(channel(0), channel(8), channel(16))
+            })
+    }
+}
+
+// BVH nodes tested by the camera ray, as a heat colour reaching white at `max_nodes`.
+#[derive(Debug, Clone, Copy)]
+pub struct BvhCostIntegrator {
+    pub max_nodes: u32,
+}
+
+impl Default for BvhCostIntegrator {
+    fn default() -> Self {
+        Self { max_nodes: 100 }
+    }
+}
+
+impl Integrator for BvhCostIntegrator {
+    fn name(&self) -> &'static str {
+        "bvh-cost"
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        *rays += 1;
+        let nodes =
+            scene
+                .world
+                .bvh_nodes_visited(ray, Interval::new(0.001, f64::INFINITY), sampler);
+        heat_colour(nodes as f64 / self.max_nodes.max(1) as f64)
+    }
+}
+
+// Fraction of cosine-weighted directions from the first hit that escape within `distance`,
+// which defaults to a tenth of the world's bounding box diagonal. One direction per sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AmbientOcclusionIntegrator {
+    pub distance: Option<f64>,
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn name(&self) -> &'static str {
+        "ao"
+    }
+
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &Scene<'_>,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Colour {
+        let Some(rec) = scene.first_hit(ray, sampler, rays) else {
+            return Colour::one();
+        };
+
+        let distance = self.distance.unwrap_or_else(|| 0.1 * scene.extent());
+        let direction = Onb::new(rec.normal).transform(Vec3::random_cosine_direction(sampler));
+        let occlusion_ray = Ray::new(rec.p, direction, ray.time());
+
+        *rays += 1;
+        let mut occluder = HitRecord::new();
+        if scene.world.hit(
+            &occlusion_ray,
+            Interval::new(0.001, distance),
+            &mut occluder,
+            sampler,
+        ) {
+            Colour::zero()
+        } else {
+            Colour::one()
+        }
+    }
+}
@@ -1,36 +1,80 @@
 #[warn(clippy::pedantic)]
 pub mod aabb;
+pub mod aov;
 pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
 pub mod colour;
 pub mod constant_medium;
+pub mod denoise;
+pub mod film;
+pub mod filter;
+pub mod framebuffer;
+pub mod gltf_model;
 pub mod hittable;
 pub mod hittable_list;
+pub mod integrator;
 pub mod interval;
+pub mod mat4;
 pub mod material;
+pub mod model;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod pdf;
 pub mod perlin;
+pub mod ply;
+pub mod progress;
 pub mod quad;
 pub mod ray;
+pub mod rng;
 pub mod rtw_image;
+pub mod sampler;
 pub mod sphere;
+pub mod stl;
 pub mod texture;
+pub mod tile;
+pub mod triangle;
+pub mod triangle_mesh;
 pub mod vec3;
 
 pub use aabb::*;
+pub use aov::*;
 pub use bvh::*;
 pub use camera::*;
+pub use checkpoint::*;
 pub use colour::*;
 pub use constant_medium::*;
+pub use denoise::*;
+pub use film::*;
+pub use filter::*;
+pub use framebuffer::*;
+pub use gltf_model::*;
 pub use hittable::*;
 pub use hittable_list::*;
+pub use integrator::*;
 pub use interval::*;
+pub use mat4::*;
 pub use material::*;
+pub use model::*;
+pub use obj::*;
+pub use onb::*;
+pub use output::*;
+pub use pdf::*;
 pub use perlin::*;
+pub use ply::*;
+pub use progress::*;
 pub use quad::*;
 pub use ray::*;
+pub use rng::*;
 pub use rtw_image::*;
+pub use sampler::*;
 pub use sphere::*;
+pub use stl::*;
 pub use texture::*;
+pub use tile::*;
+pub use triangle::*;
+pub use triangle_mesh::*;
 pub use vec3::*;
 
 pub use std::f64::consts::PI;
@@ -39,15 +83,3 @@ pub use std::sync::Arc;
 pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
-
-pub fn random_double() -> f64 {
-    rand::random::<f64>()
-}
-
-pub fn random_double_range(min: f64, max: f64) -> f64 {
-    min + (max - min) * random_double()
-}
-
-pub fn random_int_range(min: i32, max: i32) -> i32 {
-    random_double_range(min as f64, (max + 1) as f64) as i32
-}
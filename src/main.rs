@@ -1,24 +1,835 @@
 use ray_tracing_in_one_weekend::*;
+use std::error::Error;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+type SceneFn = fn(&Options) -> (HittableList, Camera);
+
+const SCENES: &[(&str, SceneFn, &str)] = &[
+    (
+        "bouncing_spheres",
+        bouncing_spheres,
+        "Book one cover: random spheres with motion blur",
+    ),
+    (
+        "checkered_spheres",
+        checkered_spheres,
+        "Two spheres with a checker texture",
+    ),
+    ("earth", earth, "Image-textured globe"),
+    ("perlin_spheres", perlin_spheres, "Perlin noise marble"),
+    ("quads", quads, "Five coloured quads"),
+    (
+        "simple_light",
+        simple_light,
+        "Perlin spheres lit by area lights",
+    ),
+    ("cornell_box", cornell_box, "Classic Cornell box"),
+    (
+        "cornell_smoke",
+        cornell_smoke,
+        "Cornell box with smoke blocks",
+    ),
+    ("final_scene", final_scene, "Book two cover"),
+];
+
+const DEFAULT_SCENE: &str = "final_scene";
+
+const USAGE: &str = "\
+Usage: ray_tracing_in_one_weekend [OPTIONS] [SCENE]
+
+Renders SCENE (default: final_scene) and saves it to the output path.
+
+Options:
+  -l, --list                  List the available scenes and exit
+  -m, --model <PATH>          Render a glTF, OBJ, PLY or STL model on a ground plane under a
+                              sky instead of a built-in scene
+  -w, --width <PIXELS>        Image width
+  -s, --samples <COUNT>       Samples per pixel
+  -d, --depth <BOUNCES>       Maximum ray bounce depth; paths normally end earlier through
+                              Russian roulette
+  -a, --aspect-ratio <RATIO>  Aspect ratio, either a number or W/H (e.g. 16/9)
+  -o, --output <PATH>         Output image path (default: scene.png)
+  -f, --format <FORMAT>       Output format; inferred from the output extension if omitted.
+                              Formats other than PNG, PPM and OpenEXR cannot hold the render
+                              metadata, which is written to a .txt file beside the image instead
+  -e, --exposure <STOPS>      Exposure adjustment in stops for 8-bit outputs (default: 0)
+  -t, --tone-map <OPERATOR>   Tone mapping for 8-bit outputs: clamp (default), reinhard,
+                              reinhard-extended, hable or aces
+      --tile-size <PIXELS>    Edge length of the square render tiles (default: 32)
+      --tile-order <ORDER>    Tile order: scanline (default), spiral or hilbert
+  -q, --quiet                 Do not draw the progress bar
+      --seed <N>              Random seed; the same seed renders the same image (default: 0)
+      --sampler <SAMPLER>     Sample generator: independent (default), stratified, halton or
+                              sobol
+      --integrator <NAME>     What each sample computes: path (default) for the lit image, or
+                              the debug views normals, depth, uv, material-id, bvh-cost or ao
+      --clamp <MAX>           Scale down any sample brighter than MAX in some channel, trading
+                              bias for fewer fireflies (default: off)
+      --clamp-bounce <MAX>    Likewise for the light each bounce after the first hit adds
+      --regularize <ROUGHNESS>
+                              Treat specular bounces after a diffuse one as glossy with
+                              ROUGHNESS in (0, 1], so light sampling can reach caustics
+      --filter <FILTER>       Splat samples through a reconstruction filter: box, tent,
+                              gaussian, mitchell or lanczos (default: average each pixel's own
+                              samples)
+      --filter-radius <PIXELS>
+                              Filter radius (default: 0.5 box, 1 tent, 1.5 gaussian, 2 mitchell,
+                              3 lanczos)
+      --aovs <PASSES>         Also record the comma-separated passes albedo, normal, depth,
+                              position, object-id, direct and indirect (or all); they go into
+                              OpenEXR outputs as extra channels, else to OUTPUT.<pass>.exr
+      --denoise               Filter the image (and previews) with an edge-avoiding a-trous
+                              denoiser guided by the albedo and normal passes
+      --adaptive <ERROR>      Stop sampling a pixel once its relative error falls below ERROR
+                              (e.g. 0.01); --samples becomes the per-pixel maximum
+      --min-samples <COUNT>   Samples every pixel takes before adaptive sampling may stop it
+                              (default: 16)
+      --heatmap <PATH>        Also save an image of the samples taken per pixel to PATH
+      --time-budget <TIME>    Render progressively until TIME has passed (e.g. 90, 90s, 10m
+                              or 2h); --samples, if given, still caps the sample count
+      --error-target <ERROR>  Render progressively until the mean relative pixel error falls
+                              below ERROR (e.g. 0.01); --samples, if given, still caps it
+  -p, --progressive <SAMPLES> Render in passes of SAMPLES samples per pixel, saving a preview
+                              to the output path as it goes
+      --preview-every <PASSES>
+                              Save a progressive preview every PASSES passes
+      --preview-seconds <SECONDS>
+                              Save a progressive preview every SECONDS seconds (default: 10)
+  -c, --checkpoint <PATH>     Save the render state to PATH alongside every preview and at the
+                              end; implies --progressive 1 unless given
+  -r, --resume                Continue from the --checkpoint file up to --samples
+  -h, --help                  Print this help and exit";
+
+struct Options {
+    scene: String,
+    model: Option<PathBuf>,
+    image_width: Option<u32>,
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<u32>,
+    aspect_ratio: Option<f64>,
+    output: PathBuf,
+    format: Option<OutputFormat>,
+    display_transform: DisplayTransform,
+    tile_size: Option<u32>,
+    tile_order: Option<TileOrder>,
+    quiet: bool,
+    seed: u64,
+    sampler: Option<String>,
+    integrator: Option<String>,
+    clamp: Option<f64>,
+    clamp_bounce: Option<f64>,
+    regularize: Option<f64>,
+    filter: Option<String>,
+    filter_radius: Option<f64>,
+    aovs: Vec<Aov>,
+    denoise: bool,
+    adaptive_threshold: Option<f64>,
+    min_samples: Option<u32>,
+    heatmap: Option<PathBuf>,
+    time_budget: Option<Duration>,
+    error_target: Option<f64>,
+    pass_samples: Option<u32>,
+    preview_every: Option<u32>,
+    preview_seconds: Option<f64>,
+    checkpoint: Option<PathBuf>,
+    resume: bool,
+    list: bool,
+    help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scene: DEFAULT_SCENE.to_string(),
+            model: None,
+            image_width: None,
+            samples_per_pixel: None,
+            max_depth: None,
+            aspect_ratio: None,
+            output: PathBuf::from("scene.png"),
+            format: None,
+            display_transform: DisplayTransform::default(),
+            tile_size: None,
+            tile_order: None,
+            quiet: false,
+            seed: 0,
+            sampler: None,
+            integrator: None,
+            clamp: None,
+            clamp_bounce: None,
+            regularize: None,
+            filter: None,
+            filter_radius: None,
+            aovs: Vec::new(),
+            denoise: false,
+            adaptive_threshold: None,
+            min_samples: None,
+            heatmap: None,
+            time_budget: None,
+            error_target: None,
+            pass_samples: None,
+            preview_every: None,
+            preview_seconds: None,
+            checkpoint: None,
+            resume: false,
+            list: false,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut opts = Self::default();
+        let mut scene = None;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| {
+                inline_value
+                    .map(str::to_string)
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("missing value for '{}'", name))
+            };
+
+            match flag.as_str() {
+                "-l" | "--list" => opts.list = true,
+                "-h" | "--help" => opts.help = true,
+                "-m" | "--model" => opts.model = Some(PathBuf::from(value(&flag)?)),
+                "-w" | "--width" => opts.image_width = Some(parse_positive(&flag, &value(&flag)?)?),
+                "-s" | "--samples" => {
+                    opts.samples_per_pixel = Some(parse_positive(&flag, &value(&flag)?)?)
+                }
+                "-d" | "--depth" => opts.max_depth = Some(parse_positive(&flag, &value(&flag)?)?),
+                "-a" | "--aspect-ratio" => {
+                    opts.aspect_ratio = Some(parse_aspect_ratio(&value(&flag)?)?)
+                }
+                "-o" | "--output" => opts.output = PathBuf::from(value(&flag)?),
+                "-f" | "--format" => opts.format = Some(parse_format(&value(&flag)?)?),
+                "-e" | "--exposure" => {
+                    opts.display_transform.exposure = parse_exposure(&value(&flag)?)?
+                }
+                "-t" | "--tone-map" => {
+                    opts.display_transform.tone_mapper = parse_tone_mapper(&value(&flag)?)?
+                }
+                "--tile-size" => opts.tile_size = Some(parse_positive(&flag, &value(&flag)?)?),
+                "--tile-order" => opts.tile_order = Some(parse_tile_order(&value(&flag)?)?),
+                "-q" | "--quiet" => opts.quiet = true,
+                "--seed" => opts.seed = parse_seed(&value(&flag)?)?,
+                "--sampler" => opts.sampler = Some(parse_sampler(&value(&flag)?)?),
+                "--integrator" => opts.integrator = Some(parse_integrator(&value(&flag)?)?),
+                "--clamp" => opts.clamp = Some(parse_radiance(&flag, &value(&flag)?)?),
+                "--clamp-bounce" => {
+                    opts.clamp_bounce = Some(parse_radiance(&flag, &value(&flag)?)?)
+                }
+                "--regularize" => opts.regularize = Some(parse_roughness(&flag, &value(&flag)?)?),
+                "--filter" => opts.filter = Some(parse_filter(&value(&flag)?)?),
+                "--filter-radius" => {
+                    opts.filter_radius = Some(parse_radius(&flag, &value(&flag)?)?)
+                }
+                "--aovs" => opts.aovs = parse_aovs(&value(&flag)?)?,
+                "--denoise" => opts.denoise = true,
+                "--adaptive" => {
+                    opts.adaptive_threshold = Some(parse_threshold(&flag, &value(&flag)?)?)
+                }
+                "--min-samples" => opts.min_samples = Some(parse_positive(&flag, &value(&flag)?)?),
+                "--heatmap" => opts.heatmap = Some(PathBuf::from(value(&flag)?)),
+                "--time-budget" => opts.time_budget = Some(parse_duration(&flag, &value(&flag)?)?),
+                "--error-target" => {
+                    opts.error_target = Some(parse_threshold(&flag, &value(&flag)?)?)
+                }
+                "-p" | "--progressive" => {
+                    opts.pass_samples = Some(parse_positive(&flag, &value(&flag)?)?)
+                }
+                "--preview-every" => {
+                    opts.preview_every = Some(parse_positive(&flag, &value(&flag)?)?)
+                }
+                "--preview-seconds" => {
+                    opts.preview_seconds = Some(parse_seconds(&flag, &value(&flag)?)?)
+                }
+                "-c" | "--checkpoint" => opts.checkpoint = Some(PathBuf::from(value(&flag)?)),
+                "-r" | "--resume" => opts.resume = true,
+                _ if flag.starts_with('-') => return Err(format!("unknown option '{}'", flag)),
+                _ if scene.is_none() => scene = Some(arg),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+
+        // A model takes the scene's place, and its path stands in for the scene name in the
+        // metadata and checkpoints.
+        match (scene, &opts.model) {
+            (Some(_), Some(_)) => {
+                return Err("'--model' cannot be combined with a scene".to_string());
+            }
+            (Some(scene), None) => opts.scene = scene,
+            (None, Some(model)) => opts.scene = model.display().to_string(),
+            (None, None) => {}
+        }
+
+        if opts.resume && opts.checkpoint.is_none() {
+            return Err("'--resume' needs a '--checkpoint' file".to_string());
+        }
+        if opts.min_samples.is_some() && opts.adaptive_threshold.is_none() {
+            return Err("'--min-samples' needs '--adaptive'".to_string());
+        }
+        let firefly_options =
+            opts.clamp.is_some() || opts.clamp_bounce.is_some() || opts.regularize.is_some();
+        if firefly_options
+            && opts
+                .integrator
+                .as_deref()
+                .is_some_and(|name| name != "path")
+        {
+            return Err(
+                "'--clamp', '--clamp-bounce' and '--regularize' need the path integrator"
+                    .to_string(),
+            );
+        }
+        if opts.filter_radius.is_some() && opts.filter.is_none() {
+            return Err("'--filter-radius' needs '--filter'".to_string());
+        }
+        if opts.time_budget.is_some() && opts.error_target.is_some() {
+            return Err("'--time-budget' and '--error-target' cannot be combined".to_string());
+        }
+        let open_ended = opts.time_budget.is_some() || opts.error_target.is_some();
+        if (open_ended || opts.checkpoint.is_some()) && opts.pass_samples.is_none() {
+            opts.pass_samples = Some(1);
+        }
+
+        Ok(opts)
+    }
+}
+
+fn parse_positive(flag: &str, value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive integer",
+            value, flag
+        )),
+    }
+}
+
+fn parse_seed(value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid seed '{}': expected a non-negative integer", value))
+}
+
+fn parse_seconds(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(s) if s.is_finite() && s > 0.0 => Ok(s),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive number of seconds",
+            value, flag
+        )),
+    }
+}
+
+fn parse_threshold(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(t) if t.is_finite() && t > 0.0 => Ok(t),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive relative error",
+            value, flag
+        )),
+    }
+}
 
-fn main() {
-    let case = 9;
+fn parse_radius(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(r) if r.is_finite() && r > 0.0 => Ok(r),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive radius in pixels",
+            value, flag
+        )),
+    }
+}
 
-    match case {
-        0 => final_scene(400, 250, 4),
-        1 => bouncing_spheres(),
-        2 => checkered_spheres(),
-        3 => earth(),
-        4 => perlin_spheres(),
-        5 => quads(),
-        6 => simple_light(),
-        7 => cornell_box(),
-        8 => cornell_smoke(),
-        9 => final_scene(800, 10000, 40),
-        _ => panic!("Invalid case"),
+fn parse_radiance(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(r) if r.is_finite() && r > 0.0 => Ok(r),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a positive radiance",
+            value, flag
+        )),
     }
 }
 
-fn bouncing_spheres() {
+fn parse_roughness(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(r) if r > 0.0 && r <= 1.0 => Ok(r),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a roughness in (0, 1]",
+            value, flag
+        )),
+    }
+}
+
+// Seconds, optionally suffixed with s, m or h.
+fn parse_duration(flag: &str, value: &str) -> Result<Duration, String> {
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1.0),
+        Some((i, 'm')) => (&value[..i], 60.0),
+        Some((i, 'h')) => (&value[..i], 3600.0),
+        _ => (value, 1.0),
+    };
+
+    match number.parse::<f64>() {
+        Ok(n) if n.is_finite() && n > 0.0 => Ok(Duration::from_secs_f64(n * unit)),
+        _ => Err(format!(
+            "invalid value '{}' for '{}': expected a duration such as 90, 90s, 10m or 2h",
+            value, flag
+        )),
+    }
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once('/') {
+        Some((w, h)) => w
+            .trim()
+            .parse::<f64>()
+            .and_then(|w| Ok(w / h.trim().parse::<f64>()?)),
+        None => value.parse::<f64>(),
+    };
+
+    match ratio {
+        Ok(r) if r.is_finite() && r > 0.0 => Ok(r),
+        _ => Err(format!(
+            "invalid aspect ratio '{}': expected a positive number or W/H",
+            value
+        )),
+    }
+}
+
+fn parse_format(value: &str) -> Result<OutputFormat, String> {
+    OutputFormat::from_name(value).ok_or_else(|| {
+        let names: Vec<_> = OutputFormat::ALL.iter().map(|f| f.name()).collect();
+        format!(
+            "unknown format '{}': expected one of {}",
+            value,
+            names.join(", ")
+        )
+    })
+}
+
+fn parse_exposure(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(stops) if stops.is_finite() => Ok(stops),
+        _ => Err(format!(
+            "invalid exposure '{}': expected a number of stops",
+            value
+        )),
+    }
+}
+
+fn parse_tone_mapper(value: &str) -> Result<Arc<dyn ToneMapper>, String> {
+    tone_mapper_from_name(value).ok_or_else(|| {
+        format!(
+            "unknown tone mapping operator '{}': expected one of {}",
+            value,
+            TONE_MAPPER_NAMES.join(", ")
+        )
+    })
+}
+
+fn parse_tile_order(value: &str) -> Result<TileOrder, String> {
+    TileOrder::from_name(value).ok_or_else(|| {
+        let names: Vec<_> = TileOrder::ALL.iter().map(|o| o.name()).collect();
+        format!(
+            "unknown tile order '{}': expected one of {}",
+            value,
+            names.join(", ")
+        )
+    })
+}
+
+// Only the name is checked here: the stratified sampler is sized from the scene's sample count,
+// which is not known until the scene is built.
+fn parse_sampler(value: &str) -> Result<String, String> {
+    let name = value.to_ascii_lowercase();
+    if SAMPLER_NAMES.contains(&name.as_str()) {
+        Ok(name)
+    } else {
+        Err(format!(
+            "unknown sampler '{}': expected one of {}",
+            value,
+            SAMPLER_NAMES.join(", ")
+        ))
+    }
+}
+
+fn parse_integrator(value: &str) -> Result<String, String> {
+    let name = value.to_ascii_lowercase();
+    if INTEGRATOR_NAMES.contains(&name.as_str()) {
+        Ok(name)
+    } else {
+        Err(format!(
+            "unknown integrator '{}': expected one of {}",
+            value,
+            INTEGRATOR_NAMES.join(", ")
+        ))
+    }
+}
+
+fn parse_filter(value: &str) -> Result<String, String> {
+    let name = value.to_ascii_lowercase();
+    if FILTER_NAMES.contains(&name.as_str()) {
+        Ok(name)
+    } else {
+        Err(format!(
+            "unknown filter '{}': expected one of {}",
+            value,
+            FILTER_NAMES.join(", ")
+        ))
+    }
+}
+
+fn parse_aovs(value: &str) -> Result<Vec<Aov>, String> {
+    if value.eq_ignore_ascii_case("all") {
+        return Ok(Aov::ALL.to_vec());
+    }
+    let mut aovs = Vec::new();
+    for name in value.split(',').map(str::trim) {
+        let aov = Aov::from_name(name).ok_or_else(|| {
+            let names: Vec<_> = Aov::ALL.iter().map(|a| a.name()).collect();
+            format!(
+                "unknown AOV '{}': expected all or a list of {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+        if !aovs.contains(&aov) {
+            aovs.push(aov);
+        }
+    }
+    Ok(aovs)
+}
+
+fn main() -> ExitCode {
+    let opts = match Options::parse(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if opts.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    if opts.list {
+        for (name, _, description) in SCENES {
+            println!("{:<20} {}", name, description);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let build = match &opts.model {
+        Some(_) => None,
+        None => match SCENES.iter().find(|(name, _, _)| *name == opts.scene) {
+            Some((_, build, _)) => Some(build),
+            None => {
+                eprintln!(
+                    "error: unknown scene '{}' (use --list to see the available scenes)",
+                    opts.scene
+                );
+                return ExitCode::from(2);
+            }
+        },
+    };
+
+    let format = match opts
+        .format
+        .map_or_else(|| OutputFormat::from_path(&opts.output), Ok)
+    {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("error: {} (pass --format to choose one)", err);
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(Err(err)) = opts.heatmap.as_deref().map(OutputFormat::from_path) {
+        eprintln!("error: heatmap: {}", err);
+        return ExitCode::from(2);
+    }
+
+    let (world, mut cam) = match (build, &opts.model) {
+        (Some(build), _) => build(&opts),
+        (None, Some(path)) => match load_model(path) {
+            Ok(model) => model_scene(&opts, model),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, None) => unreachable!("a scene or model was chosen above"),
+    };
+    cam.display_transform = opts.display_transform.clone();
+    cam.seed = opts.seed;
+    if let Some(sampler) = &opts.sampler {
+        cam.sampler = sampler_from_name(sampler, cam.samples_per_pixel).unwrap();
+    }
+    if let Some(integrator) = &opts.integrator {
+        cam.integrator = integrator_from_name(integrator).unwrap();
+    }
+    if opts.clamp.is_some() || opts.clamp_bounce.is_some() || opts.regularize.is_some() {
+        cam.integrator = Arc::new(PathIntegrator {
+            max_sample_radiance: opts.clamp,
+            max_bounce_radiance: opts.clamp_bounce,
+            regularize: opts.regularize,
+        });
+    }
+    if let Some(filter) = &opts.filter {
+        cam.filter = filter_from_name(filter, opts.filter_radius);
+    }
+    // The denoiser's guides are recorded even when they are not written out.
+    cam.aovs = opts.aovs.clone();
+    if opts.denoise {
+        for aov in [Aov::Albedo, Aov::Normal] {
+            if !cam.aovs.contains(&aov) {
+                cam.aovs.push(aov);
+            }
+        }
+    }
+    if let Some(threshold) = opts.adaptive_threshold {
+        let defaults = AdaptiveSampling::default();
+        cam.adaptive = Some(AdaptiveSampling {
+            min_samples: opts.min_samples.unwrap_or(defaults.min_samples),
+            threshold,
+        });
+    }
+    if let Some(budget) = opts.time_budget {
+        cam.termination = Termination::TimeBudget(budget);
+    }
+    if let Some(target) = opts.error_target {
+        cam.termination = Termination::ErrorTarget(target);
+    }
+    // The scene's sample count only sizes the stratified sampler's strata when the render is
+    // open-ended; an explicit --samples stays as a cap.
+    if cam.termination != Termination::SampleCount && opts.samples_per_pixel.is_none() {
+        cam.samples_per_pixel = u32::MAX;
+    }
+    if let Some(tile_size) = opts.tile_size {
+        cam.tile_size = tile_size;
+    }
+    if let Some(tile_order) = opts.tile_order {
+        cam.tile_order = tile_order;
+    }
+
+    let result = match opts.pass_samples {
+        Some(samples_per_pass) => {
+            let settings = ProgressiveSettings {
+                samples_per_pass,
+                preview_every_passes: opts.preview_every,
+                preview_interval: match (opts.preview_every, opts.preview_seconds) {
+                    (Some(_), None) => None,
+                    (_, seconds) => Some(Duration::from_secs_f64(seconds.unwrap_or(10.0))),
+                },
+            };
+            render_progressive(&cam, &world, &settings, &opts, format)
+        }
+        None => render_tiles(&cam, &world, &opts, format),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn render_tiles(
+    cam: &Camera,
+    world: &dyn Hittable,
+    opts: &Options,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    let mut bar = ProgressBar::new();
+    let accumulator = cam.render_tiles(world, |progress| {
+        if !opts.quiet {
+            bar.update(progress);
+        }
+    });
+
+    let metadata = metadata(cam, opts, &accumulator, start.elapsed());
+    save_render(
+        &beauty(&accumulator, opts),
+        &accumulator,
+        &opts.output,
+        format,
+        &cam.display_transform,
+        &opts.aovs,
+        &metadata,
+    )?;
+    finish(cam, &accumulator, opts, start.elapsed())
+}
+
+fn beauty(accumulator: &Accumulator, opts: &Options) -> Framebuffer {
+    if opts.denoise {
+        Denoiser::default().denoise(accumulator)
+    } else {
+        accumulator.resolve()
+    }
+}
+
+fn metadata(
+    cam: &Camera,
+    opts: &Options,
+    accumulator: &Accumulator,
+    elapsed: Duration,
+) -> Vec<(String, String)> {
+    let mut metadata = vec![
+        ("Software".to_string(), env!("CARGO_PKG_NAME").to_string()),
+        ("Scene".to_string(), opts.scene.clone()),
+        ("Seed".to_string(), cam.seed.to_string()),
+        (
+            "Sampler".to_string(),
+            opts.sampler.as_deref().unwrap_or("independent").to_string(),
+        ),
+        (
+            "Integrator".to_string(),
+            opts.integrator.as_deref().unwrap_or("path").to_string(),
+        ),
+        (
+            "Samples per pixel".to_string(),
+            accumulator.samples_summary(),
+        ),
+        ("Render time".to_string(), format_duration(elapsed)),
+    ];
+    if let Some(max) = opts.clamp {
+        metadata.push(("Sample clamp".to_string(), max.to_string()));
+    }
+    if let Some(max) = opts.clamp_bounce {
+        metadata.push(("Bounce clamp".to_string(), max.to_string()));
+    }
+    if let Some(roughness) = opts.regularize {
+        metadata.push((
+            "Regularization roughness".to_string(),
+            roughness.to_string(),
+        ));
+    }
+    if let Some(filter) = &cam.filter {
+        metadata.push((
+            "Filter".to_string(),
+            format!("{} (radius {})", filter.name(), filter.radius()),
+        ));
+    }
+    if opts.denoise {
+        metadata.push(("Denoiser".to_string(), "a-trous".to_string()));
+    }
+    if let Termination::ErrorTarget(_) = cam.termination {
+        metadata.push((
+            "Mean relative error".to_string(),
+            format!("{:.4}", accumulator.mean_relative_error()),
+        ));
+    }
+    metadata
+}
+
+fn finish(
+    cam: &Camera,
+    accumulator: &Accumulator,
+    opts: &Options,
+    elapsed: Duration,
+) -> Result<(), Box<dyn Error>> {
+    if (cam.adaptive.is_some() || cam.termination != Termination::SampleCount) && !opts.quiet {
+        eprintln!(
+            "rendered {} samples per pixel in {}",
+            accumulator.samples_summary(),
+            format_duration(elapsed)
+        );
+    }
+    if let Some(path) = &opts.heatmap {
+        let heatmap = accumulator.sample_heatmap(accumulator.max_samples());
+        let format = OutputFormat::from_path(path)?;
+        save_framebuffer(&heatmap, path, format, &DisplayTransform::default(), &[])?;
+    }
+    Ok(())
+}
+
+fn render_progressive(
+    cam: &Camera,
+    world: &dyn Hittable,
+    settings: &ProgressiveSettings,
+    opts: &Options,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let render_settings = RenderSettings::new(&opts.scene, cam);
+    let accumulator = match &opts.checkpoint {
+        Some(path) if opts.resume => {
+            let checkpoint = Checkpoint::resume(path, &render_settings)?;
+            eprintln!(
+                "resuming from {} samples per pixel",
+                checkpoint.accumulator.min_samples()
+            );
+            checkpoint.accumulator
+        }
+        _ => cam.new_accumulator(),
+    };
+
+    let start = Instant::now();
+    let save = |accumulator: &Accumulator| -> Result<(), Box<dyn Error>> {
+        save_render(
+            &beauty(accumulator, opts),
+            accumulator,
+            &opts.output,
+            format,
+            &cam.display_transform,
+            &opts.aovs,
+            &metadata(cam, opts, accumulator, start.elapsed()),
+        )?;
+        if let Some(path) = &opts.checkpoint {
+            Checkpoint::new(render_settings.clone(), accumulator.clone()).save(path)?;
+        }
+        Ok(())
+    };
+
+    let mut saved = Ok(());
+    let accumulator =
+        cam.resume_progressive(world, settings, accumulator, |progress, accumulator| {
+            match cam.termination {
+                Termination::SampleCount => eprintln!(
+                    "pass {}: {}/{} samples per pixel after {:.1}s",
+                    progress.pass,
+                    progress.samples_per_pixel,
+                    progress.target_samples_per_pixel,
+                    progress.elapsed.as_secs_f64()
+                ),
+                _ => eprintln!(
+                    "pass {}: {} samples per pixel after {:.1}s, mean relative error {:.4}",
+                    progress.pass,
+                    progress.samples_per_pixel,
+                    progress.elapsed.as_secs_f64(),
+                    accumulator.mean_relative_error()
+                ),
+            }
+            saved = save(accumulator);
+            if saved.is_ok() {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })?;
+    saved?;
+
+    save(&accumulator)?;
+    finish(cam, &accumulator, opts, start.elapsed())
+}
+
+fn bouncing_spheres(opts: &Options) -> (HittableList, Camera) {
+    let mut rng = Rng::new(opts.seed);
+
     let mut world = HittableList::empty();
 
     let ground_material = Arc::new(Lambertian::from_colour(Colour::new(0.5, 0.5, 0.5)));
@@ -32,23 +843,23 @@ fn bouncing_spheres() {
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = random_double();
+            let choose_mat = rng.random_double();
             let centre = Point3::new(
-                a as f64 + 0.9 * random_double(),
+                a as f64 + 0.9 * rng.random_double(),
                 0.2,
-                b as f64 + 0.9 * random_double(),
+                b as f64 + 0.9 * rng.random_double(),
             );
 
             let mut centre2 = centre;
 
             if (centre - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 let sphere_material: Arc<dyn Material> = if choose_mat < 0.8 {
-                    let albedo = Colour::random() * Colour::random();
-                    centre2 += Vec3::new(0.0, random_double_range(0.0, 0.5), 0.0);
+                    let albedo = Colour::random(&mut rng) * Colour::random(&mut rng);
+                    centre2 += Vec3::new(0.0, rng.random_double_range(0.0, 0.5), 0.0);
                     Arc::new(Lambertian::from_colour(albedo))
                 } else if choose_mat < 0.95 {
-                    let albedo = Colour::random_range(0.5, 1.0);
-                    let fuzz = random_double_range(0.0, 0.5);
+                    let albedo = Colour::random_range(&mut rng, 0.5, 1.0);
+                    let fuzz = rng.random_double_range(0.0, 0.5);
                     Arc::new(Metal::new(albedo, fuzz))
                 } else {
                     Arc::new(Dielectric::new(1.5))
@@ -85,10 +896,10 @@ fn bouncing_spheres() {
 
     let world = HittableList::new(Arc::new(BVHNode::from_list(world)));
 
-    let aspect_ratio: f64 = 16.0 / 9.0;
-    let image_width: u32 = 400;
-    let samples_per_pixel: u32 = 100;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(16.0 / 9.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::new(0.7, 0.8, 1.0);
 
     let vfov: f64 = 20.0;
@@ -113,10 +924,10 @@ fn bouncing_spheres() {
         background,
     );
 
-    cam.render(&world);
+    (world, cam)
 }
 
-fn checkered_spheres() {
+fn checkered_spheres(opts: &Options) -> (HittableList, Camera) {
     let mut world = HittableList::empty();
 
     let checker = Arc::new(CheckerTexture::from_colours(
@@ -140,10 +951,10 @@ fn checkered_spheres() {
 
     let world = HittableList::new(Arc::new(BVHNode::from_list(world)));
 
-    let aspect_ratio: f64 = 16.0 / 9.0;
-    let image_width: u32 = 400;
-    let samples_per_pixel: u32 = 100;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(16.0 / 9.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::new(0.7, 0.8, 1.0);
 
     let vfov: f64 = 20.0;
@@ -168,10 +979,88 @@ fn checkered_spheres() {
         background,
     );
 
-    cam.render(&world);
+    (world, cam)
+}
+
+// Frames a loaded model from the front and slightly above, or from the first camera its file
+// defines, sitting on a grey ground plane and lit by the sky and its own emissive meshes.
+fn model_scene(opts: &Options, model: Model) -> (HittableList, Camera) {
+    let bbox = model.objects.bounding_box();
+    let centre = Point3::new(
+        0.5 * (bbox.x.min + bbox.x.max),
+        0.5 * (bbox.y.min + bbox.y.max),
+        0.5 * (bbox.z.min + bbox.z.max),
+    );
+    let radius = 0.5
+        * Vec3::new(
+            bbox.x.max - bbox.x.min,
+            bbox.y.max - bbox.y.min,
+            bbox.z.max - bbox.z.min,
+        )
+        .length();
+
+    let mut world = HittableList::empty();
+    let ground = Arc::new(Lambertian::from_colour(Colour::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Quad::new(
+        Point3::new(
+            centre.x() - 50.0 * radius,
+            bbox.y.min,
+            centre.z() + 50.0 * radius,
+        ),
+        Vec3::new(100.0 * radius, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -100.0 * radius),
+        ground,
+    )));
+    world.add(Arc::new(model.objects));
+    let lights = model.lights;
+
+    let file_camera = model.cameras.first();
+    let aspect_ratio: f64 = opts
+        .aspect_ratio
+        .or(file_camera.and_then(|camera| camera.aspect_ratio))
+        .unwrap_or(16.0 / 9.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
+    let background = Colour::new(0.7, 0.8, 1.0);
+
+    let (vfov, look_from, look_at, vup) = match file_camera {
+        Some(camera) => (camera.vfov, camera.look_from, camera.look_at, camera.vup),
+        None => {
+            // Far enough back for the bounding sphere to fit the vertical field of view with a
+            // margin.
+            let vfov: f64 = 30.0;
+            let distance = 1.2 * radius / degrees_to_radians(vfov / 2.0).sin();
+            let look_from = centre + Vec3::new(0.0, 0.3, 1.0).unit_vector() * distance;
+            (vfov, look_from, centre, Vec3::new(0.0, 1.0, 0.0))
+        }
+    };
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let mut cam = Camera::new(
+        aspect_ratio,
+        image_width,
+        max_depth,
+        samples_per_pixel,
+        vfov,
+        look_from,
+        look_at,
+        vup,
+        defocus_angle,
+        focus_distance,
+        background,
+    );
+    // Emissive meshes are sampled directly; the sky is left to the materials' own bounces.
+    if !lights.objects.is_empty() {
+        cam.lights = Some(Arc::new(lights));
+    }
+
+    (world, cam)
 }
 
-fn earth() {
+fn earth(opts: &Options) -> (HittableList, Camera) {
     let earth_texture = Arc::new(ImageTexture::new(RtwImage::new("earthmap.jpg").unwrap()));
     let earth_surface = Arc::new(Lambertian::new(earth_texture));
     let globe = Arc::new(Sphere::new(
@@ -181,10 +1070,10 @@ fn earth() {
         earth_surface,
     ));
 
-    let aspect_ratio: f64 = 16.0 / 9.0;
-    let image_width: u32 = 400;
-    let samples_per_pixel: u32 = 100;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(16.0 / 9.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::new(0.7, 0.8, 1.0);
 
     let vfov: f64 = 20.0;
@@ -209,13 +1098,15 @@ fn earth() {
         background,
     );
 
-    cam.render(&HittableList::new(globe));
+    (HittableList::new(globe), cam)
 }
 
-fn perlin_spheres() {
+fn perlin_spheres(opts: &Options) -> (HittableList, Camera) {
+    let mut rng = Rng::new(opts.seed);
+
     let mut world = HittableList::empty();
 
-    let perlin_texture = Arc::new(NoiseTexture::<256>::new(4.0));
+    let perlin_texture = Arc::new(NoiseTexture::<256>::new(4.0, &mut rng));
     world.add(Arc::new(Sphere::new(
         Point3::new(0.0, -1000.0, 0.0),
         Point3::new(0.0, -1000.0, 0.0),
@@ -229,10 +1120,10 @@ fn perlin_spheres() {
         Arc::new(Lambertian::new(perlin_texture)),
     )));
 
-    let aspect_ratio: f64 = 16.0 / 9.0;
-    let image_width: u32 = 400;
-    let samples_per_pixel: u32 = 100;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(16.0 / 9.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::new(0.7, 0.8, 1.0);
 
     let vfov: f64 = 20.0;
@@ -257,10 +1148,10 @@ fn perlin_spheres() {
         background,
     );
 
-    cam.render(&world);
+    (world, cam)
 }
 
-fn quads() {
+fn quads(opts: &Options) -> (HittableList, Camera) {
     let mut world = HittableList::empty();
 
     let left_red = Arc::new(Lambertian::from_colour(Colour::new(1.0, 0.2, 0.2)));
@@ -300,10 +1191,10 @@ fn quads() {
         lower_teal,
     )));
 
-    let aspect_ratio: f64 = 1.0;
-    let image_width: u32 = 400;
-    let samples_per_pixel: u32 = 100;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(1.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::new(0.7, 0.8, 1.0);
 
     let vfov: f64 = 80.0;
@@ -328,13 +1219,15 @@ fn quads() {
         background,
     );
 
-    cam.render(&world);
+    (world, cam)
 }
 
-fn simple_light() {
+fn simple_light(opts: &Options) -> (HittableList, Camera) {
+    let mut rng = Rng::new(opts.seed);
+
     let mut world = HittableList::empty();
 
-    let pertext = Arc::new(NoiseTexture::<256>::new(4.0));
+    let pertext = Arc::new(NoiseTexture::<256>::new(4.0, &mut rng));
     world.add(Arc::new(Sphere::new(
         Point3::new(0.0, -1000.0, 0.0),
         Point3::new(0.0, -1000.0, 0.0),
@@ -349,23 +1242,29 @@ fn simple_light() {
     )));
 
     let difflight = Arc::new(DiffuseLight::from_colour(Colour::new(4.0, 4.0, 4.0)));
-    world.add(Arc::new(Sphere::new(
+    let sphere_light = Arc::new(Sphere::new(
         Point3::new(0.0, 7.0, 0.0),
         Point3::new(0.0, 7.0, 0.0),
         2.0,
         difflight.clone(),
-    )));
-    world.add(Arc::new(Quad::new(
+    ));
+    let quad_light = Arc::new(Quad::new(
         Point3::new(3.0, 1.0, -2.0),
         Vec3::new(2.0, 0.0, 0.0),
         Vec3::new(0.0, 2.0, 0.0),
         difflight,
-    )));
+    ));
+    world.add(sphere_light.clone());
+    world.add(quad_light.clone());
 
-    let aspect_ratio: f64 = 16.0 / 9.0;
-    let image_width: u32 = 400;
-    let samples_per_pixel: u32 = 100;
-    let max_depth: u32 = 50;
+    let mut lights = HittableList::empty();
+    lights.add(sphere_light);
+    lights.add(quad_light);
+
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(16.0 / 9.0);
+    let image_width: u32 = opts.image_width.unwrap_or(400);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(100);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::zero();
 
     let vfov: f64 = 20.0;
@@ -376,7 +1275,7 @@ fn simple_light() {
     let defocus_angle = 0.0;
     let focus_distance = 10.0;
 
-    let cam = Camera::new(
+    let mut cam = Camera::new(
         aspect_ratio,
         image_width,
         max_depth,
@@ -390,10 +1289,12 @@ fn simple_light() {
         background,
     );
 
-    cam.render(&world);
+    cam.lights = Some(Arc::new(lights));
+
+    (world, cam)
 }
 
-fn cornell_box() {
+fn cornell_box(opts: &Options) -> (HittableList, Camera) {
     let mut world = HittableList::empty();
 
     let red = Arc::new(Lambertian::from_colour(Colour::new(0.65, 0.05, 0.05)));
@@ -413,12 +1314,14 @@ fn cornell_box() {
         Vec3::new(0.0, 0.0, 555.0),
         red,
     )));
-    world.add(Arc::new(Quad::new(
+    let ceiling_light = Arc::new(Quad::new(
         Point3::new(343.0, 554.0, 332.0),
         Vec3::new(-130.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, -105.0),
         light,
-    )));
+    ));
+    world.add(ceiling_light.clone());
+    let lights = HittableList::new(ceiling_light);
     world.add(Arc::new(Quad::new(
         Point3::new(0.0, 0.0, 0.0),
         Vec3::new(555.0, 0.0, 0.0),
@@ -456,10 +1359,10 @@ fn cornell_box() {
     let box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
     world.add(box2);
 
-    let aspect_ratio: f64 = 1.0;
-    let image_width: u32 = 600;
-    let samples_per_pixel: u32 = 200;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(1.0);
+    let image_width: u32 = opts.image_width.unwrap_or(600);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(200);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::zero();
 
     let vfov: f64 = 40.0;
@@ -470,7 +1373,7 @@ fn cornell_box() {
     let defocus_angle = 0.0;
     let focus_distance = 10.0;
 
-    let cam = Camera::new(
+    let mut cam = Camera::new(
         aspect_ratio,
         image_width,
         max_depth,
@@ -484,10 +1387,12 @@ fn cornell_box() {
         background,
     );
 
-    cam.render(&world);
+    cam.lights = Some(Arc::new(lights));
+
+    (world, cam)
 }
 
-fn cornell_smoke() {
+fn cornell_smoke(opts: &Options) -> (HittableList, Camera) {
     let mut world = HittableList::empty();
 
     let red = Arc::new(Lambertian::from_colour(Colour::new(0.65, 0.05, 0.05)));
@@ -507,12 +1412,14 @@ fn cornell_smoke() {
         Vec3::new(0.0, 0.0, 555.0),
         red,
     )));
-    world.add(Arc::new(Quad::new(
+    let ceiling_light = Arc::new(Quad::new(
         Point3::new(113.0, 554.0, 127.0),
         Vec3::new(330.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, 305.0),
         light,
-    )));
+    ));
+    world.add(ceiling_light.clone());
+    let lights = HittableList::new(ceiling_light);
     world.add(Arc::new(Quad::new(
         Point3::new(0.0, 555.0, 0.0),
         Vec3::new(555.0, 0.0, 0.0),
@@ -559,10 +1466,10 @@ fn cornell_smoke() {
         Colour::one(),
     )));
 
-    let aspect_ratio: f64 = 1.0;
-    let image_width: u32 = 600;
-    let samples_per_pixel: u32 = 200;
-    let max_depth: u32 = 50;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(1.0);
+    let image_width: u32 = opts.image_width.unwrap_or(600);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(200);
+    let max_depth: u32 = opts.max_depth.unwrap_or(50);
     let background = Colour::zero();
 
     let vfov: f64 = 40.0;
@@ -573,7 +1480,7 @@ fn cornell_smoke() {
     let defocus_angle = 0.0;
     let focus_distance = 10.0;
 
-    let cam = Camera::new(
+    let mut cam = Camera::new(
         aspect_ratio,
         image_width,
         max_depth,
@@ -587,10 +1494,14 @@ fn cornell_smoke() {
         background,
     );
 
-    cam.render(&world);
+    cam.lights = Some(Arc::new(lights));
+
+    (world, cam)
 }
 
-fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
+fn final_scene(opts: &Options) -> (HittableList, Camera) {
+    let mut rng = Rng::new(opts.seed);
+
     let mut boxes1 = HittableList::empty();
 
     let ground = Arc::new(Lambertian::from_colour(Colour::new(0.48, 0.83, 0.53)));
@@ -604,7 +1515,7 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
             let z0 = -1000.0 + j as f64 * w;
             let y0 = 0.0;
             let x1 = x0 + w;
-            let y1 = random_double_range(1.0, 101.0);
+            let y1 = rng.random_double_range(1.0, 101.0);
             let z1 = z0 + w;
 
             boxes1.add(Quad::block(
@@ -620,12 +1531,14 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
     world.add(Arc::new(BVHNode::from_list(boxes1)));
 
     let light = Arc::new(DiffuseLight::from_colour(Colour::new(7.0, 7.0, 7.0)));
-    world.add(Arc::new(Quad::new(
+    let ceiling_light = Arc::new(Quad::new(
         Point3::new(123.0, 554.0, 147.0),
         Vec3::new(300.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, 265.0),
         light,
-    )));
+    ));
+    world.add(ceiling_light.clone());
+    let lights = HittableList::new(ceiling_light);
 
     let centre1 = Point3::new(400.0, 400.0, 200.0);
     let centre2 = centre1 + Vec3::new(30.0, 0.0, 0.0);
@@ -684,7 +1597,7 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
         100.0,
         emat,
     )));
-    let pertext = Arc::new(NoiseTexture::<256>::new(0.2));
+    let pertext = Arc::new(NoiseTexture::<256>::new(0.2, &mut rng));
     world.add(Arc::new(Sphere::new(
         Point3::new(220.0, 280.0, 300.0),
         Point3::new(220.0, 280.0, 300.0),
@@ -696,7 +1609,7 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
     let white = Arc::new(Lambertian::from_colour(Colour::new(0.73, 0.73, 0.73)));
     let ns = 1000;
     for _ in 0..ns {
-        let pt = Point3::random_range(0.0, 165.0);
+        let pt = Point3::random_range(&mut rng, 0.0, 165.0);
         boxes2.add(Arc::new(Sphere::new(pt, pt, 10.0, white.clone())));
     }
 
@@ -705,7 +1618,10 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 
-    let aspect_ratio: f64 = 1.0;
+    let aspect_ratio: f64 = opts.aspect_ratio.unwrap_or(1.0);
+    let image_width: u32 = opts.image_width.unwrap_or(800);
+    let samples_per_pixel: u32 = opts.samples_per_pixel.unwrap_or(10000);
+    let max_depth: u32 = opts.max_depth.unwrap_or(40);
     let background = Colour::zero();
 
     let vfov: f64 = 40.0;
@@ -716,7 +1632,7 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
     let defocus_angle = 0.0;
     let focus_distance = 10.0;
 
-    let cam = Camera::new(
+    let mut cam = Camera::new(
         aspect_ratio,
         image_width,
         max_depth,
@@ -730,5 +1646,7 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
         background,
     );
 
-    cam.render(&world);
+    cam.lights = Some(Arc::new(lights));
+
+    (world, cam)
 }
@@ -0,0 +1,242 @@
+use crate::*;
+use std::ops::Mul;
+
+// A 4x4 matrix acting on column vectors, stored row by row. Transforms are affine, so points
+// (an implicit w of 1) and directions (w of 0) ignore the bottom row. `a * b` applies `b` first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    rows: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    // From columns, the layout glTF and OpenGL store matrices in.
+    pub fn from_columns(columns: [[f64; 4]; 4]) -> Self {
+        Self { rows: columns }.transpose()
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Scales along each axis; a negative factor mirrors.
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotates by `angle` degrees about `axis`, anticlockwise looking down the axis towards the
+    // origin, as `RotateY` does about y.
+    pub fn rotation(axis: Vec3, angle: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees_to_radians(angle).sin_cos();
+        let t = 1.0 - cos;
+        Self::new([
+            [
+                t * a.x() * a.x() + cos,
+                t * a.x() * a.y() - sin * a.z(),
+                t * a.x() * a.z() + sin * a.y(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.y() + sin * a.z(),
+                t * a.y() * a.y() + cos,
+                t * a.y() * a.z() - sin * a.x(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.z() - sin * a.y(),
+                t * a.y() * a.z() + sin * a.x(),
+                t * a.z() * a.z() + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // The rotation of the quaternion x i + y j + z k + w, which need not be normalised.
+    pub fn from_quaternion(x: f64, y: f64, z: f64, w: f64) -> Self {
+        let norm = (x * x + y * y + z * z + w * w).sqrt();
+        let (x, y, z, w) = (x / norm, y / norm, z / norm, w / norm);
+        Self::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Moves the origin to `look_from` and turns the axes into the frame the camera builds: -z
+    // towards `look_at` and y as close to `vup` as that allows.
+    pub fn look_at(look_from: Point3, look_at: Point3, vup: Vec3) -> Self {
+        let w = (look_from - look_at).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+        Self::new([
+            [u.x(), v.x(), w.x(), look_from.x()],
+            [u.y(), v.y(), w.y(), look_from.y()],
+            [u.z(), v.z(), w.z(), look_from.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Applies `next` after this transform, so chains read in the order they take effect.
+    pub fn then(self, next: Self) -> Self {
+        next * self
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = self.rows[j][i];
+            }
+        }
+        Self::new(rows)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.gauss_jordan().0
+    }
+
+    // None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        self.gauss_jordan().1
+    }
+
+    // Reduces the matrix to the identity with partial pivoting, giving its determinant and, when
+    // that is not zero, the inverse the same row operations build from the identity.
+    fn gauss_jordan(&self) -> (f64, Option<Self>) {
+        let mut m = self.rows;
+        let mut inverse = Self::identity().rows;
+        let mut determinant = 1.0;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+                .unwrap();
+            if m[pivot][col] == 0.0 {
+                return (0.0, None);
+            }
+            if pivot != col {
+                m.swap(pivot, col);
+                inverse.swap(pivot, col);
+                determinant = -determinant;
+            }
+
+            let scale = m[col][col];
+            determinant *= scale;
+            for j in 0..4 {
+                m[col][j] /= scale;
+                inverse[col][j] /= scale;
+            }
+            for row in 0..4 {
+                let factor = m[row][col];
+                if row == col || factor == 0.0 {
+                    continue;
+                }
+                for j in 0..4 {
+                    m[row][j] -= factor * m[col][j];
+                    inverse[row][j] -= factor * inverse[col][j];
+                }
+            }
+        }
+        (determinant, Some(Self::new(inverse)))
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let [x, y, z] = [0, 1, 2].map(|i| {
+            let row = self.rows[i];
+            row[0] * p.x() + row[1] * p.y() + row[2] * p.z() + row[3]
+        });
+        Point3::new(x, y, z)
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let [x, y, z] = [0, 1, 2].map(|i| {
+            let row = self.rows[i];
+            row[0] * v.x() + row[1] * v.y() + row[2] * v.z()
+        });
+        Vec3::new(x, y, z)
+    }
+
+    // The smallest box around the transformed corners of `bbox`. Each output axis gathers the
+    // smaller and larger product from every input axis, which visits the extreme corners without
+    // transforming all eight.
+    pub fn transform_box(&self, bbox: &AABB) -> AABB {
+        if (0..3).any(|axis| bbox.axis_interval(axis).size() < 0.0) {
+            return AABB::empty();
+        }
+        let [x, y, z] = [0, 1, 2].map(|i| {
+            let row = self.rows[i];
+            let (mut min, mut max) = (row[3], row[3]);
+            for (j, &entry) in row[..3].iter().enumerate() {
+                // Skipping zeros keeps an unbounded axis from turning into 0 * inf = NaN.
+                if entry == 0.0 {
+                    continue;
+                }
+                let interval = bbox.axis_interval(j);
+                let (a, b) = (entry * interval.min, entry * interval.max);
+                min += a.min(b);
+                max += a.max(b);
+            }
+            Interval::new(min, max)
+        });
+        AABB::new(&x, &y, &z)
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Self::new(rows)
+    }
+}
@@ -1,19 +1,55 @@
 use crate::*;
 
+// What a material does with an incoming ray: either a density to sample the outgoing direction
+// from, or (with no `pdf`) a single specular ray to follow as is.
+pub struct ScatterRecord {
+    pub attenuation: Colour,
+    pub pdf: Option<Box<dyn Pdf>>,
+    pub skip_pdf_ray: Ray,
+}
+
+impl ScatterRecord {
+    pub fn new() -> Self {
+        Self {
+            attenuation: Colour::zero(),
+            pdf: None,
+            skip_pdf_ray: Ray::new(Point3::zero(), Vec3::zero(), 0.0),
+        }
+    }
+}
+
+impl Default for ScatterRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait Material: Send + Sync {
     fn scatter(
         &self,
         _ray_in: &Ray,
         _rec: &HitRecord,
-        _attenuation: &mut Colour,
-        _scattered: &mut Ray,
+        _srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
     ) -> bool {
         false
     }
 
+    // Density of the material scattering `ray_in` into `scattered`; the integrator weighs each
+    // sample by this over the density it actually sampled from.
+    fn scattering_pdf(&self, _ray_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
     fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Colour {
         Colour::zero()
     }
+
+    // Whether `emitted` can be anything but black, so loaders know which objects to sample as
+    // lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
@@ -35,21 +71,20 @@ impl Lambertian {
 impl Material for Lambertian {
     fn scatter(
         &self,
-        ray_in: &Ray,
+        _ray_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
     ) -> bool {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
-
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-
-        *scattered = Ray::new(rec.p, scatter_direction, ray_in.time());
-        *attenuation = self.texture.value(rec.u, rec.v, &rec.p);
+        srec.attenuation = self.texture.value(rec.u, rec.v, &rec.p) * rec.vertex_colour;
+        srec.pdf = Some(Box::new(CosinePdf::new(rec.normal)));
         true
     }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = rec.normal.dot(scattered.direction().unit_vector());
+        (cos_theta / PI).max(0.0)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -72,14 +107,15 @@ impl Material for Metal {
         &self,
         ray_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        sampler: &mut dyn Sampler,
     ) -> bool {
         let reflected = Vec3::reflect(&ray_in.direction(), &rec.normal).unit_vector()
-            + Vec3::random_unit_vector() * self.fuzz;
-        *scattered = Ray::new(rec.p, reflected, ray_in.time());
-        *attenuation = self.albedo;
-        scattered.direction().dot(rec.normal) > 0.0
+            + Vec3::random_unit_vector(sampler) * self.fuzz;
+        srec.attenuation = self.albedo * rec.vertex_colour;
+        srec.pdf = None;
+        srec.skip_pdf_ray = Ray::new(rec.p, reflected, ray_in.time());
+        reflected.dot(rec.normal) > 0.0
     }
 }
 
@@ -104,10 +140,11 @@ impl Material for Dielectric {
         &self,
         ray_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        sampler: &mut dyn Sampler,
     ) -> bool {
-        *attenuation = Colour::new(1.0, 1.0, 1.0);
+        srec.attenuation = Colour::new(1.0, 1.0, 1.0);
+        srec.pdf = None;
         let refraction_ratio = if rec.front_face {
             1.0 / self.refractive_index
         } else {
@@ -117,14 +154,15 @@ impl Material for Dielectric {
         let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction =
-            if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > random_double() {
-                Vec3::reflect(&unit_direction, &rec.normal)
-            } else {
-                Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
-            };
-
-        *scattered = Ray::new(rec.p, direction, ray_in.time());
+        let direction = if cannot_refract
+            || Self::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
+        {
+            Vec3::reflect(&unit_direction, &rec.normal)
+        } else {
+            Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
+        };
+
+        srec.skip_pdf_ray = Ray::new(rec.p, direction, ray_in.time());
         true
     }
 }
@@ -149,6 +187,10 @@ impl Material for DiffuseLight {
     fn emitted(&self, u: f64, v: f64, p: &Point3) -> Colour {
         self.tex.value(u, v, p)
     }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
 }
 
 pub struct Isotropic {
@@ -170,13 +212,17 @@ impl Isotropic {
 impl Material for Isotropic {
     fn scatter(
         &self,
-        ray_in: &Ray,
+        _ray_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
     ) -> bool {
-        *scattered = Ray::new(rec.p, Vec3::random_unit_vector(), ray_in.time());
-        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Box::new(SpherePdf));
         true
     }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
 }
@@ -0,0 +1,123 @@
+use crate::*;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(PathBuf, io::Error),
+    // A problem on a given line of a text file, counting from 1.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "'{}': {}", path.display(), err),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Self::Invalid(path, msg) => write!(f, "'{}': {}", path.display(), msg),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// What models are drawn in where their files give no material: a grey matching MTL's default
+// diffuse colour.
+pub(crate) fn default_material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::from_colour(default_diffuse()))
+}
+
+// A viewpoint stored in a model file.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelCamera {
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub vup: Vec3,
+    // Vertical field of view in degrees.
+    pub vfov: f64,
+    pub aspect_ratio: Option<f64>,
+}
+
+// A loaded model, with the cameras its file defines in file order (only glTF has any).
+pub struct Model {
+    pub objects: HittableList,
+    // The meshes with emissive materials, also in `objects`, for the camera to sample as lights.
+    pub lights: HittableList,
+    pub cameras: Vec<ModelCamera>,
+}
+
+impl Model {
+    pub fn new() -> Self {
+        Self {
+            objects: HittableList::empty(),
+            lights: HittableList::empty(),
+            cameras: Vec::new(),
+        }
+    }
+
+    // Adds a mesh unless it has no faces left, and to the lights too if it glows.
+    pub(crate) fn add_mesh(&mut self, mesh: TriangleMesh) {
+        if mesh.is_empty() {
+            return;
+        }
+        let mesh = Arc::new(mesh);
+        if mesh.is_emissive() {
+            self.lights.add(mesh.clone());
+        }
+        self.objects.add(mesh);
+    }
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Loads a model file, choosing the format from its extension.
+pub fn load_model(path: &Path) -> Result<Model, ModelError> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let mesh = |objects| Model {
+        objects,
+        ..Model::new()
+    };
+    let model = match extension.as_deref() {
+        Some("gltf" | "glb") => load_gltf(path)?,
+        Some("obj") => load_obj(path)?,
+        Some("ply") => mesh(load_ply(path)?),
+        Some("stl") => mesh(load_stl(path)?),
+        _ => {
+            return Err(ModelError::Invalid(
+                path.to_path_buf(),
+                "unsupported model format (expected .gltf, .glb, .obj, .ply or .stl)".to_string(),
+            ));
+        }
+    };
+    if model.objects.objects.is_empty() {
+        return Err(ModelError::Invalid(
+            path.to_path_buf(),
+            "model has no faces".to_string(),
+        ));
+    }
+    Ok(model)
+}
@@ -0,0 +1,374 @@
+use crate::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Loads a Wavefront OBJ file and the MTL libraries it names, as one triangle mesh per material.
+// Meshes whose material has an emission colour are also the model's lights.
+// Polygons are split into fans, and statements the renderer has no use for (groups, smoothing,
+// lines, free-form geometry) are skipped.
+pub fn load_obj(path: &Path) -> Result<Model, ModelError> {
+    let text = fs::read_to_string(path).map_err(|err| ModelError::Io(path.to_path_buf(), err))?;
+    let mut obj = ObjLoader::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+        let parse_error = |message| ModelError::Parse {
+            path: path.to_path_buf(),
+            line: index + 1,
+            message,
+        };
+
+        if keyword == "mtllib" {
+            if args.is_empty() {
+                return Err(parse_error("'mtllib' needs a file name".to_string()));
+            }
+            let dir = path.parent().unwrap_or(Path::new(""));
+            for name in args {
+                let materials = load_mtl(&dir.join(name)).map_err(|err| match err {
+                    ModelError::Io(_, err) => {
+                        parse_error(format!("cannot read '{}': {}", name, err))
+                    }
+                    err => err,
+                })?;
+                obj.materials.extend(materials);
+            }
+        } else {
+            obj.statement(keyword, &args).map_err(parse_error)?;
+        }
+    }
+
+    let mut model = Model::new();
+    for group in obj.groups {
+        if group.faces.is_empty() {
+            continue;
+        }
+        let mesh = TriangleMesh::new(
+            group.positions,
+            &group.faces,
+            group.has_normals.then_some(group.normals),
+            group.has_uvs.then_some(group.uvs),
+            None,
+            group.material,
+        )
+        .map_err(|message| ModelError::Invalid(path.to_path_buf(), message))?;
+        model.add_mesh(mesh);
+    }
+    Ok(model)
+}
+
+// The faces drawn with one material, with their own vertex buffers: OBJ indexes positions,
+// texture coordinates and normals separately, so each distinct combination becomes a vertex.
+struct Group {
+    material: Arc<dyn Material>,
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<[usize; 3]>,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    // Normals and UVs are only kept when every vertex of the group has them.
+    has_normals: bool,
+    has_uvs: bool,
+}
+
+impl Group {
+    fn new(material: Arc<dyn Material>) -> Self {
+        Self {
+            material,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+            vertices: HashMap::new(),
+            has_normals: true,
+            has_uvs: true,
+        }
+    }
+}
+
+struct ObjLoader {
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    groups: Vec<Group>,
+    // Index into `groups` for each material name used so far.
+    group_for_material: HashMap<String, usize>,
+    current: usize,
+}
+
+impl ObjLoader {
+    fn new() -> Self {
+        Self {
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            materials: HashMap::new(),
+            groups: vec![Group::new(default_material())],
+            group_for_material: HashMap::new(),
+            current: 0,
+        }
+    }
+
+    fn statement(&mut self, keyword: &str, args: &[&str]) -> Result<(), String> {
+        match keyword {
+            "v" => {
+                let v = parse_numbers(keyword, args, 3, 4)?;
+                self.positions.push(Point3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let vt = parse_numbers(keyword, args, 1, 3)?;
+                self.uvs.push((vt[0], vt.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let vn = parse_numbers(keyword, args, 3, 3)?;
+                self.normals.push(Vec3::new(vn[0], vn[1], vn[2]));
+            }
+            "f" => self.face(args)?,
+            "usemtl" => {
+                let name = args.join(" ");
+                let material = self
+                    .materials
+                    .get(&name)
+                    .ok_or_else(|| format!("material '{}' is not defined", name))?;
+                let groups = &mut self.groups;
+                self.current = *self.group_for_material.entry(name).or_insert_with(|| {
+                    groups.push(Group::new(material.clone()));
+                    groups.len() - 1
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn face(&mut self, args: &[&str]) -> Result<(), String> {
+        if args.len() < 3 {
+            return Err(format!(
+                "a face needs at least 3 vertices, not {}",
+                args.len()
+            ));
+        }
+
+        let mut corners = Vec::with_capacity(args.len());
+        for arg in args {
+            let mut parts = arg.split('/');
+            let position = resolve_index(parts.next(), self.positions.len(), "vertex")?
+                .ok_or_else(|| format!("face vertex '{}' has no position", arg))?;
+            let uv = resolve_index(parts.next(), self.uvs.len(), "texture coordinate")?;
+            let normal = resolve_index(parts.next(), self.normals.len(), "normal")?;
+            if parts.next().is_some() {
+                return Err(format!("malformed face vertex '{}'", arg));
+            }
+            corners.push(self.vertex(position, uv, normal));
+        }
+
+        let group = &mut self.groups[self.current];
+        for i in 1..corners.len() - 1 {
+            group.faces.push([corners[0], corners[i], corners[i + 1]]);
+        }
+        Ok(())
+    }
+
+    // The index in the current group's buffers of the vertex with these attributes.
+    fn vertex(&mut self, position: usize, uv: Option<usize>, normal: Option<usize>) -> usize {
+        let group = &mut self.groups[self.current];
+        group.has_uvs &= uv.is_some();
+        group.has_normals &= normal.is_some();
+
+        *group
+            .vertices
+            .entry((position, uv, normal))
+            .or_insert_with(|| {
+                group.positions.push(self.positions[position]);
+                group.uvs.push(uv.map_or((0.0, 0.0), |i| self.uvs[i]));
+                group
+                    .normals
+                    .push(normal.map_or(Vec3::zero(), |i| self.normals[i]));
+                group.positions.len() - 1
+            })
+    }
+}
+
+// Turns a 1-based, or negative and relative to the end, OBJ index into a 0-based one. An empty
+// or missing field has no index.
+fn resolve_index(field: Option<&str>, len: usize, what: &str) -> Result<Option<usize>, String> {
+    let Some(field) = field.filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = field
+        .parse()
+        .map_err(|_| format!("invalid {} index '{}'", what, field))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "{} index {} is out of range ({} defined so far)",
+            what, index, len
+        ));
+    }
+    Ok(Some(resolved as usize))
+}
+
+fn parse_numbers(keyword: &str, args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!(
+            "'{}' needs {} numbers, not {}",
+            keyword,
+            expected,
+            args.len()
+        ));
+    }
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f64>()
+                .ok()
+                .filter(|x| x.is_finite())
+                .ok_or_else(|| format!("invalid number '{}' in '{}'", arg, keyword))
+        })
+        .collect()
+}
+
+// MTL's default diffuse colour.
+pub(crate) fn default_diffuse() -> Colour {
+    Colour::new(0.8, 0.8, 0.8)
+}
+
+// The MTL properties the renderer maps onto its materials.
+struct MtlEntry {
+    kd: Colour,
+    ks: Colour,
+    ke: Colour,
+    ns: f64,
+    ni: Option<f64>,
+    d: f64,
+    // File name and line of the diffuse texture.
+    map_kd: Option<(String, usize)>,
+}
+
+impl MtlEntry {
+    fn new() -> Self {
+        Self {
+            kd: default_diffuse(),
+            ks: Colour::zero(),
+            ke: Colour::zero(),
+            ns: 0.0,
+            ni: None,
+            d: 1.0,
+            map_kd: None,
+        }
+    }
+
+    // Emissive materials become lights, translucent ones glass, ones whose specular colour
+    // outshines their diffuse colour metal (rougher as Ns falls), and the rest diffuse.
+    fn material(self, path: &Path) -> Result<Arc<dyn Material>, ModelError> {
+        let max = |c: Colour| c.x().max(c.y()).max(c.z());
+        if max(self.ke) > 0.0 {
+            return Ok(Arc::new(DiffuseLight::from_colour(self.ke)));
+        }
+        if self.d < 1.0 {
+            return Ok(Arc::new(Dielectric::new(self.ni.unwrap_or(1.5))));
+        }
+        if self.map_kd.is_none() && max(self.ks) > max(self.kd) {
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            return Ok(Arc::new(Metal::new(self.ks, fuzz)));
+        }
+        match self.map_kd {
+            Some((name, line)) => {
+                let texture_path = path.parent().unwrap_or(Path::new("")).join(&name);
+                let image = RtwImage::new(&texture_path.to_string_lossy()).map_err(|_| {
+                    ModelError::Parse {
+                        path: path.to_path_buf(),
+                        line,
+                        message: format!("could not load texture '{}'", name),
+                    }
+                })?;
+                Ok(Arc::new(Lambertian::new(Arc::new(ImageTexture::new(
+                    image,
+                )))))
+            }
+            None => Ok(Arc::new(Lambertian::from_colour(self.kd))),
+        }
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, Arc<dyn Material>>, ModelError> {
+    let text = fs::read_to_string(path).map_err(|err| ModelError::Io(path.to_path_buf(), err))?;
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+        let parse_error = |message| ModelError::Parse {
+            path: path.to_path_buf(),
+            line: index + 1,
+            message,
+        };
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(parse_error("'newmtl' needs a material name".to_string()));
+            }
+            if let Some((name, entry)) = current.take() {
+                materials.insert(name, entry.material(path)?);
+            }
+            current = Some((args.join(" "), MtlEntry::new()));
+            continue;
+        }
+
+        let Some((_, entry)) = current.as_mut() else {
+            if ["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "map_Kd"].contains(&keyword) {
+                return Err(parse_error(format!("'{}' before any 'newmtl'", keyword)));
+            }
+            continue;
+        };
+        let colour = |args: &[&str]| -> Result<Colour, String> {
+            let c = parse_numbers(keyword, args, 1, 3)?;
+            match c.len() {
+                1 => Ok(Colour::new(c[0], c[0], c[0])),
+                3 => Ok(Colour::new(c[0], c[1], c[2])),
+                _ => Err(format!("'{}' needs 1 or 3 numbers, not 2", keyword)),
+            }
+        };
+        let scalar = |args: &[&str]| parse_numbers(keyword, args, 1, 1).map(|v| v[0]);
+
+        match keyword {
+            "Kd" => entry.kd = colour(&args).map_err(parse_error)?,
+            "Ks" => entry.ks = colour(&args).map_err(parse_error)?,
+            "Ke" => entry.ke = colour(&args).map_err(parse_error)?,
+            "Ns" => entry.ns = scalar(&args).map_err(parse_error)?,
+            "Ni" => entry.ni = Some(scalar(&args).map_err(parse_error)?),
+            "d" => entry.d = scalar(&args).map_err(parse_error)?,
+            "Tr" => entry.d = 1.0 - scalar(&args).map_err(parse_error)?,
+            // Options such as -s or -o come before the file name, so it is the last argument.
+            "map_Kd" => {
+                let name = args
+                    .last()
+                    .ok_or_else(|| parse_error("'map_Kd' needs a file name".to_string()))?;
+                entry.map_kd = Some((name.to_string(), index + 1));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((name, entry)) = current {
+        materials.insert(name, entry.material(path)?);
+    }
+    Ok(materials)
+}
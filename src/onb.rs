@@ -0,0 +1,38 @@
+use crate::*;
+
+// Orthonormal basis with `w` along the given normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    // Transforms from basis coordinates to world space.
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        self.axis[0] * v.x() + self.axis[1] * v.y() + self.axis[2] * v.z()
+    }
+}
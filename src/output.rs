@@ -0,0 +1,530 @@
+use crate::{Accumulator, Aov, DisplayTransform, Framebuffer};
+use image::error::{EncodingError, ImageFormatHint};
+use image::{ImageError, ImageFormat, Rgb32FImage, RgbImage};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Ppm,
+    Tga,
+    Bmp,
+    Tiff,
+    WebP,
+    Exr,
+    Hdr,
+    Pfm,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 10] = [
+        Self::Png,
+        Self::Jpeg,
+        Self::Ppm,
+        Self::Tga,
+        Self::Bmp,
+        Self::Tiff,
+        Self::WebP,
+        Self::Exr,
+        Self::Hdr,
+        Self::Pfm,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "ppm" => Some(Self::Ppm),
+            "tga" => Some(Self::Tga),
+            "bmp" => Some(Self::Bmp),
+            "tif" | "tiff" => Some(Self::Tiff),
+            "webp" => Some(Self::WebP),
+            "exr" => Some(Self::Exr),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, OutputError> {
+        let path = path.as_ref();
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_name)
+            .ok_or_else(|| OutputError::UnknownFormat(path.to_path_buf()))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+            Self::Ppm => "ppm",
+            Self::Tga => "tga",
+            Self::Bmp => "bmp",
+            Self::Tiff => "tiff",
+            Self::WebP => "webp",
+            Self::Exr => "exr",
+            Self::Hdr => "hdr",
+            Self::Pfm => "pfm",
+        }
+    }
+
+    pub fn is_hdr(self) -> bool {
+        matches!(self, Self::Exr | Self::Hdr | Self::Pfm)
+    }
+
+    // Whether the file itself can hold the metadata; the others get a sidecar (see
+    // `metadata_path`).
+    pub fn carries_metadata(self) -> bool {
+        matches!(self, Self::Png | Self::Ppm | Self::Exr)
+    }
+
+    // The image crate encoder for the formats without a writer of their own here.
+    fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            Self::Jpeg => Some(ImageFormat::Jpeg),
+            Self::Tga => Some(ImageFormat::Tga),
+            Self::Bmp => Some(ImageFormat::Bmp),
+            Self::Tiff => Some(ImageFormat::Tiff),
+            Self::WebP => Some(ImageFormat::WebP),
+            Self::Hdr => Some(ImageFormat::Hdr),
+            Self::Png | Self::Ppm | Self::Exr | Self::Pfm => None,
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    UnknownFormat(PathBuf),
+    // An 8-bit image given to a floating-point format, or the other way round.
+    IncompatibleFormat(PathBuf, OutputFormat),
+    Io(PathBuf, io::Error),
+    Encode(PathBuf, ImageError),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat(path) => {
+                write!(f, "cannot infer an image format from '{}'", path.display())
+            }
+            Self::IncompatibleFormat(path, format) => {
+                let kind = if format.is_hdr() {
+                    "8-bit"
+                } else {
+                    "floating-point"
+                };
+                write!(
+                    f,
+                    "cannot write a {} image to '{}' as {}",
+                    kind,
+                    path.display(),
+                    format
+                )
+            }
+            Self::Io(path, err) => write!(f, "failed to write '{}': {}", path.display(), err),
+            Self::Encode(path, err) => write!(f, "failed to encode '{}': {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UnknownFormat(_) | Self::IncompatibleFormat(..) => None,
+            Self::Io(_, err) => Some(err),
+            Self::Encode(_, err) => Some(err),
+        }
+    }
+}
+
+// Key/value pairs describing a render. They are stored as PNG text chunks, OpenEXR header
+// attributes and PPM comments; the other formats have nowhere to put them, so they go in a text
+// file beside the image instead.
+pub type Metadata = [(String, String)];
+
+// Floating-point formats keep the raw linear radiance; the display transform only applies to
+// the 8-bit ones.
+pub fn save_framebuffer(
+    framebuffer: &Framebuffer,
+    path: &Path,
+    format: OutputFormat,
+    display_transform: &DisplayTransform,
+    metadata: &Metadata,
+) -> Result<(), OutputError> {
+    if format.is_hdr() {
+        save_hdr_image(&framebuffer.to_rgb32f_image(), path, format, metadata)
+    } else {
+        save_image(
+            &framebuffer.to_rgb_image(display_transform),
+            path,
+            format,
+            metadata,
+        )
+    }
+}
+
+// Saves the beauty image with the requested AOVs from `accumulator`. OpenEXR output carries them
+// as extra channels named after the pass (`albedo.R`, `depth.Z`, ...); other formats cannot hold
+// the raw values, so each pass goes to its own OpenEXR file beside the image (see `aov_path`).
+pub fn save_render(
+    beauty: &Framebuffer,
+    accumulator: &Accumulator,
+    path: &Path,
+    format: OutputFormat,
+    display_transform: &DisplayTransform,
+    aovs: &[Aov],
+    metadata: &Metadata,
+) -> Result<(), OutputError> {
+    if format == OutputFormat::Exr && !aovs.is_empty() {
+        let passes: Vec<_> = aovs
+            .iter()
+            .map(|&aov| (aov, accumulator.resolve_aov(aov)))
+            .collect();
+        let file = File::create(path).map_err(|err| OutputError::Io(path.to_path_buf(), err))?;
+        let mut writer = BufWriter::new(file);
+        write_layered_exr(&mut writer, beauty, &passes, metadata)
+            .map_err(|err| encode_error(path, ImageFormat::OpenExr, err))?;
+        return writer
+            .flush()
+            .map_err(|err| OutputError::Io(path.to_path_buf(), err));
+    }
+
+    save_framebuffer(beauty, path, format, display_transform, metadata)?;
+    for &aov in aovs {
+        save_framebuffer(
+            &accumulator.resolve_aov(aov),
+            &aov_path(path, aov),
+            OutputFormat::Exr,
+            display_transform,
+            metadata,
+        )?;
+    }
+    Ok(())
+}
+
+// Where a pass is written when the image itself is not OpenEXR: `scene.png` puts the albedo in
+// `scene.albedo.exr`.
+pub fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    path.with_extension(format!("{}.exr", aov.name()))
+}
+
+// Where the metadata of a format that cannot carry it is written: `scene.jpg` puts it in
+// `scene.txt`, one `key: value` line per entry.
+pub fn metadata_path(path: &Path) -> PathBuf {
+    path.with_extension("txt")
+}
+
+fn save_metadata_sidecar(
+    path: &Path,
+    format: OutputFormat,
+    metadata: &Metadata,
+) -> Result<(), OutputError> {
+    if format.carries_metadata() || metadata.is_empty() {
+        return Ok(());
+    }
+    let path = metadata_path(path);
+    let io_err = |err| OutputError::Io(path.clone(), err);
+    let mut writer = BufWriter::new(File::create(&path).map_err(io_err)?);
+    for (key, value) in metadata {
+        writeln!(writer, "{}: {}", key, value.replace(['\r', '\n'], " ")).map_err(io_err)?;
+    }
+    writer.flush().map_err(io_err)
+}
+
+pub fn save_image(
+    image: &RgbImage,
+    path: &Path,
+    format: OutputFormat,
+    metadata: &Metadata,
+) -> Result<(), OutputError> {
+    // Checked before the file is created, so a bad call leaves any existing file alone.
+    if format.is_hdr() {
+        return Err(OutputError::IncompatibleFormat(path.to_path_buf(), format));
+    }
+
+    let file = File::create(path).map_err(|err| OutputError::Io(path.to_path_buf(), err))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        OutputFormat::Png => write_png(&mut writer, image, metadata)
+            .map_err(|err| encode_error(path, ImageFormat::Png, err))?,
+        OutputFormat::Ppm => write_ppm(&mut writer, image, metadata)
+            .map_err(|err| OutputError::Io(path.to_path_buf(), err))?,
+        _ => {
+            let image_format = format
+                .image_format()
+                .ok_or_else(|| OutputError::IncompatibleFormat(path.to_path_buf(), format))?;
+            image
+                .write_to(&mut writer, image_format)
+                .map_err(|err| OutputError::Encode(path.to_path_buf(), err))?
+        }
+    }
+
+    writer
+        .flush()
+        .map_err(|err| OutputError::Io(path.to_path_buf(), err))?;
+    save_metadata_sidecar(path, format, metadata)
+}
+
+pub fn save_hdr_image(
+    image: &Rgb32FImage,
+    path: &Path,
+    format: OutputFormat,
+    metadata: &Metadata,
+) -> Result<(), OutputError> {
+    if !format.is_hdr() {
+        return Err(OutputError::IncompatibleFormat(path.to_path_buf(), format));
+    }
+
+    let file = File::create(path).map_err(|err| OutputError::Io(path.to_path_buf(), err))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        OutputFormat::Pfm => {
+            write_pfm(&mut writer, image).map_err(|err| OutputError::Io(path.to_path_buf(), err))?
+        }
+        OutputFormat::Exr => write_exr(&mut writer, image, metadata)
+            .map_err(|err| encode_error(path, ImageFormat::OpenExr, err))?,
+        _ => {
+            let image_format = format
+                .image_format()
+                .ok_or_else(|| OutputError::IncompatibleFormat(path.to_path_buf(), format))?;
+            image
+                .write_to(&mut writer, image_format)
+                .map_err(|err| OutputError::Encode(path.to_path_buf(), err))?
+        }
+    }
+
+    writer
+        .flush()
+        .map_err(|err| OutputError::Io(path.to_path_buf(), err))?;
+    save_metadata_sidecar(path, format, metadata)
+}
+
+// Portable float map: text header, then little-endian f32 RGB rows from bottom to top.
+fn write_pfm(writer: &mut impl Write, image: &Rgb32FImage) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for row in image.rows().rev() {
+        for pixel in row {
+            for channel in pixel.0 {
+                writer.write_all(&channel.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_error(
+    path: &Path,
+    format: ImageFormat,
+    err: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> OutputError {
+    OutputError::Encode(
+        path.to_path_buf(),
+        ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(format), err)),
+    )
+}
+
+fn write_png(
+    writer: &mut impl Write,
+    image: &RgbImage,
+    metadata: &Metadata,
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // tEXt chunks only hold Latin-1, so anything beyond it (a model path in another script, say)
+    // goes in an iTXt chunk, which holds UTF-8.
+    for (key, value) in metadata {
+        if is_latin1(value) {
+            encoder.add_text_chunk(key.clone(), value.clone())?;
+        } else {
+            encoder.add_itxt_chunk(key.clone(), value.clone())?;
+        }
+    }
+    encoder.write_header()?.write_image_data(image.as_raw())
+}
+
+// Binary PPM (P6) with the metadata as header comments. The image crate's generic PNM path
+// writes PAM (P7), which most viewers expect to be called .pam.
+fn write_ppm(writer: &mut impl Write, image: &RgbImage, metadata: &Metadata) -> io::Result<()> {
+    writeln!(writer, "P6")?;
+    for (key, value) in metadata {
+        writeln!(writer, "# {}: {}", key, value.replace(['\r', '\n'], " "))?;
+    }
+    write!(writer, "{} {}\n255\n", image.width(), image.height())?;
+    writer.write_all(image.as_raw())
+}
+
+fn write_exr(
+    writer: &mut (impl Write + io::Seek),
+    image: &Rgb32FImage,
+    metadata: &Metadata,
+) -> Result<(), exr::error::Error> {
+    use exr::prelude::*;
+
+    let channels = SpecificChannels::rgb(|position: Vec2<usize>| {
+        let pixel = image.get_pixel(position.x() as u32, position.y() as u32);
+        (pixel[0], pixel[1], pixel[2])
+    });
+    let size = (image.width() as usize, image.height() as usize);
+    let layer = Layer::new(
+        size,
+        exr_attributes(metadata),
+        Encoding::FAST_LOSSLESS,
+        channels,
+    );
+
+    Image::from_layer(layer).write().to_buffered(writer)
+}
+
+// A single layer holding the beauty image as R, G and B, and every pass as `<pass>.<channel>`.
+// Object IDs are stored as integers, everything else as 32-bit floats.
+fn write_layered_exr(
+    writer: &mut (impl Write + io::Seek),
+    beauty: &Framebuffer,
+    passes: &[(Aov, Framebuffer)],
+    metadata: &Metadata,
+) -> Result<(), exr::error::Error> {
+    use exr::prelude::*;
+
+    let component = |framebuffer: &Framebuffer, c: usize| -> Vec<f32> {
+        framebuffer.pixels.iter().map(|p| p[c] as f32).collect()
+    };
+
+    let mut channels = SmallVec::new();
+    for (c, name) in ["R", "G", "B"].into_iter().enumerate() {
+        channels.push(AnyChannel::new(
+            name,
+            FlatSamples::F32(component(beauty, c)),
+        ));
+    }
+    for (aov, framebuffer) in passes {
+        for (c, channel) in aov.channels().iter().enumerate() {
+            let name = format!("{}.{}", aov.name(), channel);
+            let samples = match aov {
+                Aov::ObjectId => {
+                    FlatSamples::U32(framebuffer.pixels.iter().map(|p| p[c] as u32).collect())
+                }
+                _ => FlatSamples::F32(component(framebuffer, c)),
+            };
+            channels.push(AnyChannel::new(name.as_str(), samples));
+        }
+    }
+
+    let size = (beauty.width as usize, beauty.height as usize);
+    let layer = Layer::new(
+        size,
+        exr_attributes(metadata),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels),
+    );
+
+    Image::from_layer(layer).write().to_buffered(writer)
+}
+
+fn exr_attributes(metadata: &Metadata) -> exr::prelude::LayerAttributes {
+    use exr::prelude::*;
+
+    // OpenEXR text is Latin-1 with no UTF-8 alternative, so other characters are written as
+    // `\u{...}` escapes rather than failing the whole save.
+    let text = |s: &str| {
+        Text::new_or_none(s).unwrap_or_else(|| {
+            let escaped: String = s
+                .chars()
+                .map(|c| {
+                    if (c as u32) <= 0xff {
+                        c.to_string()
+                    } else {
+                        c.escape_unicode().to_string()
+                    }
+                })
+                .collect();
+            Text::from(escaped.as_str())
+        })
+    };
+
+    let mut attributes = LayerAttributes::default();
+    for (key, value) in metadata {
+        attributes
+            .other
+            .insert(text(key), AttributeValue::Text(text(value)));
+    }
+    attributes
+}
+
+fn is_latin1(s: &str) -> bool {
+    s.chars().all(|c| (c as u32) <= 0xff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Vec<(String, String)> {
+        vec![("Scene".to_string(), "/tmp/rv/模型.obj".to_string())]
+    }
+
+    #[test]
+    fn png_keeps_non_latin1_metadata_as_utf8() {
+        let path = std::env::temp_dir().join("output_test_metadata.png");
+        save_image(&RgbImage::new(2, 2), &path, OutputFormat::Png, &metadata()).unwrap();
+
+        let reader = png::Decoder::new(io::BufReader::new(File::open(&path).unwrap()))
+            .read_info()
+            .unwrap();
+        let text = &reader.info().utf8_text;
+        assert_eq!(text.len(), 1);
+        assert_eq!(text[0].keyword, "Scene");
+        assert_eq!(text[0].get_text().unwrap(), "/tmp/rv/模型.obj");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn jpeg_metadata_goes_to_a_sidecar() {
+        let path = std::env::temp_dir().join("output_test_metadata.jpg");
+        save_image(&RgbImage::new(2, 2), &path, OutputFormat::Jpeg, &metadata()).unwrap();
+
+        let sidecar = metadata_path(&path);
+        assert_eq!(
+            std::fs::read_to_string(&sidecar).unwrap(),
+            "Scene: /tmp/rv/模型.obj\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&sidecar).unwrap();
+    }
+
+    #[test]
+    fn exr_escapes_non_latin1_metadata() {
+        let path = std::env::temp_dir().join("output_test_metadata.exr");
+        save_hdr_image(
+            &Rgb32FImage::new(2, 2),
+            &path,
+            OutputFormat::Exr,
+            &metadata(),
+        )
+        .unwrap();
+
+        let meta = exr::meta::MetaData::read_from_file(&path, false).unwrap();
+        let attributes = &meta.headers[0].own_attributes.other;
+        let scene = attributes.get(&exr::prelude::Text::from("Scene")).unwrap();
+        assert_eq!(
+            scene,
+            &exr::meta::attribute::AttributeValue::Text(exr::prelude::Text::from(
+                "/tmp/rv/\\u{6a21}\\u{578b}.obj"
+            ))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -8,18 +8,18 @@ pub struct Perlin<const N: usize> {
 }
 
 impl<const N: usize> Perlin<N> {
-    pub fn new() -> Self {
+    pub fn new(rng: &mut Rng) -> Self {
         let mut randvec = [Vec3::zero(); N];
         for val in randvec.iter_mut() {
-            *val = Vec3::random_range(-1.0, 1.0).unit_vector();
+            *val = Vec3::random_range(rng, -1.0, 1.0).unit_vector();
         }
 
         let mut perm_x = [0; N];
         let mut perm_y = [0; N];
         let mut perm_z = [0; N];
-        Self::perlin_generate_perm(&mut perm_x);
-        Self::perlin_generate_perm(&mut perm_y);
-        Self::perlin_generate_perm(&mut perm_z);
+        Self::perlin_generate_perm(&mut perm_x, rng);
+        Self::perlin_generate_perm(&mut perm_y, rng);
+        Self::perlin_generate_perm(&mut perm_z, rng);
 
         Self {
             randvec,
@@ -69,17 +69,17 @@ impl<const N: usize> Perlin<N> {
         accum.abs()
     }
 
-    fn perlin_generate_perm(perm: &mut [i32; N]) {
+    fn perlin_generate_perm(perm: &mut [i32; N], rng: &mut Rng) {
         for (i, val) in perm.iter_mut().enumerate() {
             *val = i as i32;
         }
 
-        Self::permute(perm);
+        Self::permute(perm, rng);
     }
 
-    fn permute(perm: &mut [i32; N]) {
+    fn permute(perm: &mut [i32; N], rng: &mut Rng) {
         for i in (0..N).rev() {
-            let target = random_int_range(0, i as i32) as usize;
+            let target = rng.random_int_range(0, i as i32) as usize;
             perm.swap(i, target);
         }
     }
@@ -109,6 +109,6 @@ impl<const N: usize> Perlin<N> {
 
 impl<const N: usize> Default for Perlin<N> {
     fn default() -> Self {
-        Self::new()
+        Self::new(&mut Rng::default())
     }
 }
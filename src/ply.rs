@@ -0,0 +1,460 @@
+use crate::*;
+use std::fs;
+use std::path::Path;
+
+// Loads a PLY file, ASCII or binary in either byte order, as one triangle mesh. Vertex normals,
+// texture coordinates and colours are used when the file has them; colours are taken to be sRGB.
+// Elements other than vertices and faces are read past and ignored.
+pub fn load_ply(path: &Path) -> Result<HittableList, ModelError> {
+    let data = fs::read(path).map_err(|err| ModelError::Io(path.to_path_buf(), err))?;
+    let header = parse_header(&data).map_err(|(line, message)| ModelError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    })?;
+
+    let mut body = match header.format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(&data[header.length..]).map_err(|_| {
+                ModelError::Invalid(path.to_path_buf(), "ASCII body is not text".to_string())
+            })?;
+            Body::Ascii {
+                lines: text.lines(),
+                line: header.lines,
+                tokens: Vec::new(),
+            }
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Body::Binary {
+            data: &data[header.length..],
+            offset: 0,
+            big_endian: header.format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = MeshBuffers::default();
+    for element in &header.elements {
+        let layout = VertexLayout::new(element);
+        for index in 0..element.count {
+            body.read_element(element, &mut mesh, &layout)
+                .map_err(|message| body.error(path, element, index, message))?;
+        }
+    }
+
+    // Vertex colours multiply the material's albedo, so coloured meshes get a white one to show
+    // them as stored.
+    let material: Arc<dyn Material> = if mesh.colours.is_some() {
+        Arc::new(Lambertian::from_colour(Colour::one()))
+    } else {
+        default_material()
+    };
+    let mut model = HittableList::empty();
+    let mesh = TriangleMesh::new(
+        mesh.positions,
+        &mesh.faces,
+        mesh.normals,
+        mesh.uvs,
+        mesh.colours,
+        material,
+    )
+    .map_err(|message| ModelError::Invalid(path.to_path_buf(), message))?;
+    if !mesh.is_empty() {
+        model.add(Arc::new(mesh));
+    }
+    Ok(model)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::I8),
+            "uchar" | "uint8" => Some(Self::U8),
+            "short" | "int16" => Some(Self::I16),
+            "ushort" | "uint16" => Some(Self::U16),
+            "int" | "int32" => Some(Self::I32),
+            "uint" | "uint32" => Some(Self::U32),
+            "float" | "float32" => Some(Self::F32),
+            "double" | "float64" => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    // What a colour stored in this type is divided by to bring it into [0, 1].
+    fn colour_scale(self) -> f64 {
+        match self {
+            Self::U8 | Self::I8 => 255.0,
+            Self::U16 | Self::I16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    // Bytes and lines up to and including `end_header`.
+    length: usize,
+    lines: usize,
+}
+
+// Errors carry the header line they were found on.
+fn parse_header(data: &[u8]) -> Result<Header, (usize, String)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+    let mut number = 0;
+
+    loop {
+        let Some(end) = data[offset..].iter().position(|&b| b == b'\n') else {
+            return Err((number + 1, "header has no 'end_header'".to_string()));
+        };
+        let line = String::from_utf8_lossy(&data[offset..offset + end]);
+        offset += end + 1;
+        number += 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        if number == 1 {
+            if tokens != ["ply"] {
+                return Err((number, "not a PLY file".to_string()));
+            }
+            continue;
+        }
+        match tokens.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err((number, format!("unknown format '{}'", name))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| (number, format!("invalid element count '{}'", count)))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", rest @ ..] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| (number, "property before any element".to_string()))?;
+                let scalar = |name: &str| {
+                    Scalar::from_name(name)
+                        .ok_or_else(|| (number, format!("unknown property type '{}'", name)))
+                };
+                let (kind, name) = match rest {
+                    ["list", count, item, name] => (
+                        PropertyKind::List {
+                            count: scalar(count)?,
+                            item: scalar(item)?,
+                        },
+                        name,
+                    ),
+                    [kind, name] => (PropertyKind::Scalar(scalar(kind)?), name),
+                    _ => return Err((number, "malformed property".to_string())),
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
+            ["end_header"] => break,
+            _ => return Err((number, format!("unexpected header line '{}'", line.trim()))),
+        }
+    }
+
+    let format = format.ok_or_else(|| (number, "header has no 'format'".to_string()))?;
+    Ok(Header {
+        format,
+        elements,
+        length: offset,
+        lines: number,
+    })
+}
+
+#[derive(Default)]
+struct MeshBuffers {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colours: Option<Vec<Colour>>,
+    faces: Vec<[usize; 3]>,
+}
+
+// Where the attributes the renderer uses sit among an element's properties.
+struct VertexLayout {
+    position: Option<[usize; 3]>,
+    normal: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+    colour: Option<([usize; 3], f64)>,
+    face_indices: Option<usize>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Self {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+        };
+        let find_all =
+            |names: [&[&str]; 3]| Some([find(names[0])?, find(names[1])?, find(names[2])?]);
+
+        let colour = find_all([
+            &["red", "diffuse_red"],
+            &["green", "diffuse_green"],
+            &["blue", "diffuse_blue"],
+        ])
+        .map(|indices| {
+            let scale = match element.properties[indices[0]].kind {
+                PropertyKind::Scalar(scalar) => scalar.colour_scale(),
+                PropertyKind::List { .. } => 1.0,
+            };
+            (indices, scale)
+        });
+
+        Self {
+            position: find_all([&["x"], &["y"], &["z"]]),
+            normal: find_all([&["nx"], &["ny"], &["nz"]]),
+            uv: find(&["u", "s", "texture_u", "texture_s"])
+                .zip(find(&["v", "t", "texture_v", "texture_t"]))
+                .map(|(u, v)| [u, v]),
+            colour,
+            face_indices: find(&["vertex_indices", "vertex_index"]),
+        }
+    }
+}
+
+// The data after the header, read one element at a time.
+enum Body<'a> {
+    Ascii {
+        lines: std::str::Lines<'a>,
+        line: usize,
+        tokens: Vec<&'a str>,
+    },
+    Binary {
+        data: &'a [u8],
+        offset: usize,
+        big_endian: bool,
+    },
+}
+
+impl Body<'_> {
+    // Reads one instance of `element`, adding it to the mesh if it is a vertex or a face.
+    fn read_element(
+        &mut self,
+        element: &Element,
+        mesh: &mut MeshBuffers,
+        layout: &VertexLayout,
+    ) -> Result<(), String> {
+        if let Self::Ascii {
+            lines,
+            line,
+            tokens,
+        } = self
+        {
+            let text = lines.next().ok_or("unexpected end of file")?;
+            *line += 1;
+            *tokens = text.split_whitespace().rev().collect();
+        }
+
+        // Scalars hold one value; lists hold their items.
+        let mut values: Vec<Vec<f64>> = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            values.push(match property.kind {
+                PropertyKind::Scalar(scalar) => vec![self.read(scalar)?],
+                PropertyKind::List { count, item } => {
+                    let count = self.read(count)?;
+                    if count < 0.0 || count.fract() != 0.0 {
+                        return Err(format!("invalid list length {}", count));
+                    }
+                    (0..count as usize)
+                        .map(|_| self.read(item))
+                        .collect::<Result<_, _>>()?
+                }
+            });
+        }
+
+        if let Self::Ascii { tokens, .. } = self
+            && !tokens.is_empty()
+        {
+            return Err(format!(
+                "{} has more values than its {} properties",
+                element.name,
+                element.properties.len()
+            ));
+        }
+
+        match element.name.as_str() {
+            "vertex" => add_vertex(&values, layout, mesh),
+            "face" => add_face(&values, layout, mesh),
+            _ => Ok(()),
+        }
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Self::Ascii { tokens, .. } => {
+                let token = tokens.pop().ok_or("too few values")?;
+                token
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(|| format!("invalid number '{}'", token))
+            }
+            Self::Binary {
+                data,
+                offset,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                let bytes = data
+                    .get(*offset..*offset + size)
+                    .ok_or("unexpected end of file")?;
+                *offset += size;
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                let value = match scalar {
+                    Scalar::I8 => buffer[0] as i8 as f64,
+                    Scalar::U8 => buffer[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::U32 => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::F32 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::F64 => f64::from_le_bytes(buffer),
+                };
+                if !value.is_finite() {
+                    return Err("invalid number".to_string());
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    // ASCII errors point at the line; binary ones at the element.
+    fn error(&self, path: &Path, element: &Element, index: usize, message: String) -> ModelError {
+        match self {
+            Self::Ascii { line, .. } => ModelError::Parse {
+                path: path.to_path_buf(),
+                line: *line,
+                message,
+            },
+            Self::Binary { .. } => ModelError::Invalid(
+                path.to_path_buf(),
+                format!("{} {}: {}", element.name, index, message),
+            ),
+        }
+    }
+}
+
+fn add_vertex(
+    values: &[Vec<f64>],
+    layout: &VertexLayout,
+    mesh: &mut MeshBuffers,
+) -> Result<(), String> {
+    let get = |i: usize| values[i].first().copied().unwrap_or(0.0);
+    let [x, y, z] = layout.position.ok_or("vertices have no x, y and z")?;
+    mesh.positions.push(Point3::new(get(x), get(y), get(z)));
+
+    // Every vertex shares the layout, so the optional attributes are either present for all of
+    // them or for none.
+    if let Some([x, y, z]) = layout.normal {
+        mesh.normals
+            .get_or_insert_with(Vec::new)
+            .push(Vec3::new(get(x), get(y), get(z)));
+    }
+    if let Some([u, v]) = layout.uv {
+        mesh.uvs.get_or_insert_with(Vec::new).push((get(u), get(v)));
+    }
+    if let Some(([r, g, b], scale)) = layout.colour {
+        let channel = |i| srgb_to_linear((get(i) / scale).clamp(0.0, 1.0));
+        mesh.colours.get_or_insert_with(Vec::new).push(Colour::new(
+            channel(r),
+            channel(g),
+            channel(b),
+        ));
+    }
+    Ok(())
+}
+
+// Polygons are split into fans around their first vertex.
+fn add_face(
+    values: &[Vec<f64>],
+    layout: &VertexLayout,
+    mesh: &mut MeshBuffers,
+) -> Result<(), String> {
+    let indices = &values[layout.face_indices.ok_or("faces have no vertex_indices")?];
+    if indices.len() < 3 {
+        return Err(format!(
+            "a face needs at least 3 vertices, not {}",
+            indices.len()
+        ));
+    }
+    if let Some(bad) = indices.iter().find(|i| **i < 0.0 || i.fract() != 0.0) {
+        return Err(format!("invalid vertex index {}", bad));
+    }
+    for i in 1..indices.len() - 1 {
+        mesh.faces.push([
+            indices[0] as usize,
+            indices[i] as usize,
+            indices[i + 1] as usize,
+        ]);
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileProgress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub rays: u64,
+    pub elapsed: Duration,
+}
+
+impl TileProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.tiles_total == 0 {
+            1.0
+        } else {
+            self.tiles_done as f64 / self.tiles_total as f64
+        }
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        if fraction <= 0.0 {
+            return None;
+        }
+        Some(self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.rays as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct ProgressBar {
+    width: usize,
+    min_interval: Duration,
+    last_draw: Option<Instant>,
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        Self {
+            width: 30,
+            min_interval: Duration::from_millis(100),
+            last_draw: None,
+        }
+    }
+
+    pub fn update(&mut self, progress: &TileProgress) {
+        let finished = progress.tiles_done == progress.tiles_total;
+        if !finished
+            && self
+                .last_draw
+                .is_some_and(|t| t.elapsed() < self.min_interval)
+        {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+
+        let filled = (progress.fraction() * self.width as f64).round() as usize;
+        let eta = progress
+            .eta()
+            .map_or_else(|| "--".to_string(), format_duration);
+
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r[{}{}] {}/{} tiles  ETA {}  {:.2} Mrays/s ",
+            "#".repeat(filled),
+            "-".repeat(self.width - filled),
+            progress.tiles_done,
+            progress.tiles_total,
+            eta,
+            progress.rays_per_second() / 1e6
+        );
+        if finished {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m{:02}s", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
@@ -9,6 +9,8 @@ pub struct Quad {
     bbox: AABB,
     normal: Vec3,
     d: f64,
+    area: f64,
+    id: u32,
 }
 
 impl Quad {
@@ -18,6 +20,7 @@ impl Quad {
         let normal = n.unit_vector();
         let d = normal.dot(q);
         let w = n / n.dot(n);
+        let area = n.length();
         let mut quad = Self {
             q,
             u,
@@ -27,6 +30,8 @@ impl Quad {
             bbox,
             normal,
             d,
+            area,
+            id: next_object_id(),
         };
         quad.set_bounding_box();
         quad
@@ -103,7 +108,13 @@ impl Quad {
 }
 
 impl Hittable for Quad {
-    fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
         let denom = self.normal.dot(ray.direction());
         if denom.abs() < 1e-8 {
             return false;
@@ -126,6 +137,8 @@ impl Hittable for Quad {
         rec.t = t;
         rec.p = intersection;
         rec.material = self.material.clone();
+        rec.object_id = self.id;
+        rec.vertex_colour = Colour::one();
         rec.set_face_normal(ray, self.normal);
 
         true
@@ -134,4 +147,27 @@ impl Hittable for Quad {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::new(*origin, *direction, 0.0),
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+            &mut Rng::new(0),
+        ) {
+            return 0.0;
+        }
+
+        // Convert the uniform density over the quad's area into one over solid angle.
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (a, b) = sampler.get_2d();
+        let p = self.q + self.u * a + self.v * b;
+        p - *origin
+    }
 }
@@ -0,0 +1,80 @@
+// PCG32 (XSH-RR variant). Implemented here rather than taken from a crate so the sequence for a
+// given seed never changes underneath saved renders and checkpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Rng {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0)
+    }
+
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    // Each pixel sample gets its own generator, so the image does not depend on which thread
+    // renders which pixel or in how many passes.
+    pub fn for_sample(seed: u64, pixel: u64, sample: u64) -> Self {
+        Self::with_stream(Self::hash(&[seed, pixel, sample]), pixel)
+    }
+
+    pub fn from_hash(values: &[u64]) -> Self {
+        Self::new(Self::hash(values))
+    }
+
+    pub fn hash(values: &[u64]) -> u64 {
+        values
+            .iter()
+            .fold(0x853c49e6748fea9b, |h, &v| splitmix64(h ^ splitmix64(v)))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    // Uniform in [0, 1) with the full 53 bits of mantissa.
+    pub fn random_double(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    pub fn random_double_range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.random_double()
+    }
+
+    pub fn random_int_range(&mut self, min: i32, max: i32) -> i32 {
+        self.random_double_range(min as f64, (max + 1) as f64) as i32
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
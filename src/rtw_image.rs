@@ -1,4 +1,5 @@
-use image::ImageError;
+use crate::srgb_to_linear;
+use image::{DynamicImage, ImageError};
 use std::env;
 use std::path::{Path, PathBuf};
 
@@ -47,30 +48,45 @@ impl RtwImage {
         ))
     }
 
-    fn load(&mut self, filename: &Path) -> Result<Self, ImageError> {
-        let img = image::open(filename)?;
-        self.width = img.width();
-        self.height = img.height();
+    // Takes an image already decoded in memory, such as one embedded in a model file.
+    pub fn from_image(img: &DynamicImage) -> Self {
+        let mut image = RtwImage {
+            bytes_per_pixel: 3,
+            width: img.width(),
+            height: img.height(),
+            float_data: None,
+            byte_data: None,
+        };
 
         // Convert image to RGB and store as 8-bit pixel data
         let rgb_image = img.to_rgb8();
-        self.byte_data = Some(
+        image.byte_data = Some(
             rgb_image
                 .clone()
                 .into_raw()
                 .iter_mut()
-                .map(|byte| ((*byte as f64).powi(2) / 255.0) as u8)
+                .map(|byte| (255.0 * srgb_to_linear(*byte as f64 / 255.0)) as u8)
                 .collect(),
         );
 
-        // Convert 8-bit pixel data to floating-point data (linear gamma)
-        self.float_data = Some(
+        // Convert 8-bit pixel data to floating-point data (linear, undoing the sRGB curve)
+        image.float_data = Some(
             rgb_image
                 .pixels()
-                .flat_map(|pixel| pixel.0.iter().map(|&byte| (byte as f32 / 255.0).powi(2)))
+                .flat_map(|pixel| {
+                    pixel
+                        .0
+                        .iter()
+                        .map(|&byte| srgb_to_linear(byte as f64 / 255.0) as f32)
+                })
                 .collect(),
         );
 
+        image
+    }
+
+    fn load(&mut self, filename: &Path) -> Result<Self, ImageError> {
+        *self = Self::from_image(&image::open(filename)?);
         Ok(self.clone())
     }
 
@@ -0,0 +1,347 @@
+use crate::*;
+
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+pub trait Sampler {
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+impl Sampler for Rng {
+    fn get_1d(&mut self) -> f64 {
+        self.random_double()
+    }
+}
+
+// A sampler the camera restarts for every pixel sample. Each call to get_1d or get_2d moves on
+// to the next dimension of that sample's point.
+pub trait PixelSampler: Sampler + Send + Sync {
+    // The name `sampler_from_name` knows it by.
+    fn name(&self) -> &'static str;
+
+    // The name followed by any settings that change the sequence, for checkpoints to compare.
+    fn description(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn start_pixel_sample(&mut self, seed: u64, pixel: u64, sample: u64);
+
+    fn clone_box(&self) -> Box<dyn PixelSampler>;
+}
+
+pub fn sampler_from_name(name: &str, samples_per_pixel: u32) -> Option<Arc<dyn PixelSampler>> {
+    match name.to_ascii_lowercase().as_str() {
+        "independent" => Some(Arc::new(IndependentSampler::new())),
+        "stratified" => Some(Arc::new(StratifiedSampler::new(samples_per_pixel))),
+        "halton" => Some(Arc::new(HaltonSampler::new())),
+        "sobol" => Some(Arc::new(SobolSampler::new())),
+        _ => None,
+    }
+}
+
+pub const SAMPLER_NAMES: [&str; 4] = ["independent", "stratified", "halton", "sobol"];
+
+#[derive(Debug, Clone, Default)]
+pub struct IndependentSampler {
+    rng: Rng,
+}
+
+impl IndependentSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn get_1d(&mut self) -> f64 {
+        self.rng.random_double()
+    }
+}
+
+impl PixelSampler for IndependentSampler {
+    fn name(&self) -> &'static str {
+        "independent"
+    }
+
+    fn start_pixel_sample(&mut self, seed: u64, pixel: u64, sample: u64) {
+        self.rng = Rng::for_sample(seed, pixel, sample);
+    }
+
+    fn clone_box(&self) -> Box<dyn PixelSampler> {
+        Box::new(self.clone())
+    }
+}
+
+// Jittered sampling: every dimension visits its strata in a per-pixel random order. Samples past
+// `samples_per_pixel` start a fresh round with a new order.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    seed: u64,
+    pixel: u64,
+    sample: u64,
+    dimension: u64,
+    rng: Rng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed: 0,
+            pixel: 0,
+            sample: 0,
+            dimension: 0,
+            rng: Rng::default(),
+        }
+    }
+
+    fn stratum(&mut self, strata: u32) -> u32 {
+        let n = self.samples_per_pixel as u64;
+        let round = self.sample / n;
+        let permutation = Rng::hash(&[self.seed, self.pixel, self.dimension, round]);
+        self.dimension += 1;
+        permutation_element((self.sample % n) as u32, strata, permutation as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn get_1d(&mut self) -> f64 {
+        let n = self.samples_per_pixel;
+        let stratum = self.stratum(n);
+        ((stratum as f64 + self.rng.random_double()) / n as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let n = self.samples_per_pixel;
+        let nx = (n as f64).sqrt().ceil() as u32;
+        let ny = n.div_ceil(nx);
+        let stratum = self.stratum(nx * ny);
+        let x = ((stratum % nx) as f64 + self.rng.random_double()) / nx as f64;
+        let y = ((stratum / nx) as f64 + self.rng.random_double()) / ny as f64;
+        (x.min(ONE_MINUS_EPSILON), y.min(ONE_MINUS_EPSILON))
+    }
+}
+
+impl PixelSampler for StratifiedSampler {
+    fn name(&self) -> &'static str {
+        "stratified"
+    }
+
+    // The strata are sized from the sample count the sampler was made for.
+    fn description(&self) -> String {
+        format!("{} {}", self.name(), self.samples_per_pixel)
+    }
+
+    fn start_pixel_sample(&mut self, seed: u64, pixel: u64, sample: u64) {
+        self.seed = seed;
+        self.pixel = pixel;
+        self.sample = sample;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(seed, pixel, sample);
+    }
+
+    fn clone_box(&self) -> Box<dyn PixelSampler> {
+        Box::new(self.clone())
+    }
+}
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// The Halton sequence indexed by sample number, with each pixel's digits randomly shifted so
+// neighbouring pixels do not share the same points. Dimensions past the prime table fall back to
+// independent random numbers.
+#[derive(Debug, Clone, Default)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    sample: u64,
+    dimension: usize,
+    rng: Rng,
+}
+
+impl HaltonSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn get_1d(&mut self) -> f64 {
+        if self.dimension >= PRIMES.len() {
+            return self.rng.random_double();
+        }
+        let base = PRIMES[self.dimension];
+        let scramble = Rng::hash(&[self.seed, self.pixel, self.dimension as u64]);
+        self.dimension += 1;
+        scrambled_radical_inverse(base, self.sample, scramble)
+    }
+}
+
+impl PixelSampler for HaltonSampler {
+    fn name(&self) -> &'static str {
+        "halton"
+    }
+
+    fn start_pixel_sample(&mut self, seed: u64, pixel: u64, sample: u64) {
+        self.seed = seed;
+        self.pixel = pixel;
+        self.sample = sample;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(seed, pixel, sample);
+    }
+
+    fn clone_box(&self) -> Box<dyn PixelSampler> {
+        Box::new(self.clone())
+    }
+}
+
+// Owen-scrambled Sobol points following Burley, "Practical Hash-based Owen Scrambling" (2020):
+// every dimension pair is its own shuffled, scrambled copy of the first two Sobol dimensions.
+#[derive(Debug, Clone, Default)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel: u64,
+    sample: u64,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_pattern(&mut self) -> (u32, u32) {
+        let pattern = Rng::hash(&[self.seed, self.pixel, self.dimension]);
+        self.dimension += 1;
+        let index = nested_uniform_scramble(self.sample as u32, pattern as u32);
+        (index, (pattern >> 32) as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn get_1d(&mut self) -> f64 {
+        let (index, scramble) = self.next_pattern();
+        let x = nested_uniform_scramble(index.reverse_bits(), scramble);
+        to_unit_float(x)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, scramble) = self.next_pattern();
+        let x = nested_uniform_scramble(index.reverse_bits(), scramble);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), scramble ^ 0x9e3779b9);
+        (to_unit_float(x), to_unit_float(y))
+    }
+}
+
+impl PixelSampler for SobolSampler {
+    fn name(&self) -> &'static str {
+        "sobol"
+    }
+
+    fn start_pixel_sample(&mut self, seed: u64, pixel: u64, sample: u64) {
+        self.seed = seed;
+        self.pixel = pixel;
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    fn clone_box(&self) -> Box<dyn PixelSampler> {
+        Box::new(self.clone())
+    }
+}
+
+fn to_unit_float(x: u32) -> f64 {
+    (x as f64 / 4294967296.0).min(ONE_MINUS_EPSILON)
+}
+
+// Generator matrix for x + 1: v_i = v_{i-1} ^ (v_{i-1} >> 1), starting from the top bit.
+fn sobol_second_dimension(index: u32) -> u32 {
+    let mut result = 0;
+    let mut v = 1u32 << 31;
+    let mut i = index;
+    while i != 0 {
+        if i & 1 != 0 {
+            result ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn scrambled_radical_inverse(base: u64, mut index: u64, scramble: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut result = 0.0;
+    let mut digit_index = 0;
+
+    // Keep adding digits until they can no longer change the result, since the shifted
+    // trailing zeros are not zero any more.
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let digit = index % base;
+        index /= base;
+        let shifted = (digit + Rng::hash(&[scramble, digit_index]) % base) % base;
+        inv_base_m *= inv_base;
+        result += shifted as f64 * inv_base_m;
+        digit_index += 1;
+    }
+
+    result.min(ONE_MINUS_EPSILON)
+}
+
+// Kensler's hash-based permutation from "Correlated Multi-Jittered Sampling" (2013): the `i`th
+// element of a random permutation of 0..len chosen by `p`.
+fn permutation_element(mut i: u32, len: u32, p: u32) -> u32 {
+    let mut w = len.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+
+    (i.wrapping_add(p)) % len
+}
@@ -5,6 +5,7 @@ pub struct Sphere {
     radius: f64,
     material: Arc<dyn Material>,
     bbox: AABB,
+    id: u32,
 }
 
 impl Sphere {
@@ -19,6 +20,7 @@ impl Sphere {
             radius,
             material,
             bbox: AABB::from_boxes(&box1, &box2),
+            id: next_object_id(),
         }
     }
 
@@ -28,10 +30,29 @@ impl Sphere {
         *u = phi / (2.0 * PI);
         *v = theta / PI;
     }
+
+    // A direction within the cone the sphere subtends from a point `distance_squared` away,
+    // about +z.
+    fn random_to_sphere(radius: f64, distance_squared: f64, sampler: &mut dyn Sampler) -> Vec3 {
+        let (r1, r2) = sampler.get_2d();
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Vec3::new(x, y, z)
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
         let current_centre = self.centre.at(ray.time());
         let oc = current_centre - ray.origin();
         let a = ray.direction().length_squared();
@@ -55,6 +76,8 @@ impl Hittable for Sphere {
             rec.set_face_normal(ray, outward_normal);
             Self::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
             rec.material = self.material.clone();
+            rec.object_id = self.id;
+            rec.vertex_colour = Colour::one();
 
             true
         }
@@ -63,4 +86,42 @@ impl Hittable for Sphere {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // Light sampling treats the sphere as stationary at its time-zero position. From inside
+    // the sphere every direction hits it, so both fall back to the uniform sphere density.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let distance_squared = (self.centre.at(0.0) - *origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
+
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::new(*origin, *direction, 0.0),
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+            &mut Rng::new(0),
+        ) {
+            return 0.0;
+        }
+
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let direction = self.centre.at(0.0) - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector(sampler);
+        }
+
+        let uvw = Onb::new(direction);
+        uvw.transform(Self::random_to_sphere(
+            self.radius,
+            distance_squared,
+            sampler,
+        ))
+    }
 }
@@ -0,0 +1,135 @@
+use crate::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Loads an STL file, ASCII or binary, as one triangle mesh. STL repeats each vertex for every
+// facet that uses it, so identical positions are merged into shared vertices. The stored facet
+// normals are ignored in favour of the winding, which they are meant to agree with.
+pub fn load_stl(path: &Path) -> Result<HittableList, ModelError> {
+    let data = fs::read(path).map_err(|err| ModelError::Io(path.to_path_buf(), err))?;
+
+    // Some binary files also start with "solid", so the size, which a binary file states
+    // exactly, decides.
+    let binary_size = data
+        .get(80..84)
+        .map(|count| 84 + 50 * u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    let facets = if binary_size == Some(data.len()) {
+        binary_facets(&data)
+    } else if data.starts_with(b"solid") {
+        ascii_facets(path, &data)?
+    } else {
+        return Err(ModelError::Invalid(
+            path.to_path_buf(),
+            match binary_size {
+                Some(size) => format!(
+                    "binary STL should be {} bytes for its facet count, not {}",
+                    size,
+                    data.len()
+                ),
+                None => "file is too short to be an STL".to_string(),
+            },
+        ));
+    };
+
+    let mut positions = Vec::new();
+    let mut shared = HashMap::new();
+    let faces: Vec<[usize; 3]> = facets
+        .iter()
+        .map(|facet| {
+            facet.map(|p| {
+                let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+                *shared.entry(key).or_insert_with(|| {
+                    positions.push(p);
+                    positions.len() - 1
+                })
+            })
+        })
+        .collect();
+
+    let mut model = HittableList::empty();
+    let mesh = TriangleMesh::new(positions, &faces, None, None, None, default_material())
+        .map_err(|message| ModelError::Invalid(path.to_path_buf(), message))?;
+    if !mesh.is_empty() {
+        model.add(Arc::new(mesh));
+    }
+    Ok(model)
+}
+
+// Each record is a normal and three vertices as little-endian f32s, then two attribute bytes.
+fn binary_facets(data: &[u8]) -> Vec<[Point3; 3]> {
+    data[84..]
+        .chunks_exact(50)
+        .map(|record| {
+            let float =
+                |i: usize| f32::from_le_bytes(record[4 * i..4 * i + 4].try_into().unwrap()) as f64;
+            [0, 1, 2].map(|v| Point3::new(float(3 + 3 * v), float(4 + 3 * v), float(5 + 3 * v)))
+        })
+        .collect()
+}
+
+fn ascii_facets(path: &Path, data: &[u8]) -> Result<Vec<[Point3; 3]>, ModelError> {
+    let text = std::str::from_utf8(data).map_err(|_| {
+        ModelError::Invalid(path.to_path_buf(), "ASCII STL is not text".to_string())
+    })?;
+
+    let mut facets = Vec::new();
+    let mut facet: Option<Vec<Point3>> = None;
+    for (index, line) in text.lines().enumerate() {
+        let parse_error = |message| ModelError::Parse {
+            path: path.to_path_buf(),
+            line: index + 1,
+            message,
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("facet") => {
+                if facet.is_some() {
+                    return Err(parse_error("'facet' inside another facet".to_string()));
+                }
+                facet = Some(Vec::with_capacity(3));
+            }
+            Some("vertex") => {
+                let vertices = facet
+                    .as_mut()
+                    .ok_or_else(|| parse_error("'vertex' outside a facet".to_string()))?;
+                let v = parse_coordinates(&tokens[1..]).map_err(parse_error)?;
+                vertices.push(Point3::new(v[0], v[1], v[2]));
+            }
+            Some("endfacet") => {
+                let vertices = facet
+                    .take()
+                    .ok_or_else(|| parse_error("'endfacet' outside a facet".to_string()))?;
+                let [a, b, c] = vertices[..] else {
+                    return Err(parse_error(format!(
+                        "a facet needs 3 vertices, not {}",
+                        vertices.len()
+                    )));
+                };
+                facets.push([a, b, c]);
+            }
+            _ => {}
+        }
+    }
+    if facet.is_some() {
+        return Err(ModelError::Parse {
+            path: path.to_path_buf(),
+            line: text.lines().count(),
+            message: "file ends inside a facet".to_string(),
+        });
+    }
+    Ok(facets)
+}
+
+fn parse_coordinates(args: &[&str]) -> Result<[f64; 3], String> {
+    let [x, y, z] = args else {
+        return Err(format!("'vertex' needs 3 numbers, not {}", args.len()));
+    };
+    let number = |arg: &str| {
+        arg.parse::<f64>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .ok_or_else(|| format!("invalid number '{}' in 'vertex'", arg))
+    };
+    Ok([number(x)?, number(y)?, number(z)?])
+}
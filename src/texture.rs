@@ -98,9 +98,9 @@ pub struct NoiseTexture<const N: usize> {
 }
 
 impl<const N: usize> NoiseTexture<N> {
-    pub fn new(scale: f64) -> Self {
+    pub fn new(scale: f64, rng: &mut Rng) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Perlin::new(rng),
             scale,
         }
     }
@@ -0,0 +1,133 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width() as usize * self.height() as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    Scanline,
+    Spiral,
+    Hilbert,
+}
+
+impl TileOrder {
+    pub const ALL: [TileOrder; 3] = [Self::Scanline, Self::Spiral, Self::Hilbert];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "scanline" => Some(Self::Scanline),
+            "spiral" => Some(Self::Spiral),
+            "hilbert" => Some(Self::Hilbert),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scanline => "scanline",
+            Self::Spiral => "spiral",
+            Self::Hilbert => "hilbert",
+        }
+    }
+}
+
+pub fn tiles(width: u32, height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let cols = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let coords = match order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|ty| (0..cols).map(move |tx| (tx, ty)))
+            .collect(),
+        TileOrder::Spiral => spiral(cols, rows),
+        TileOrder::Hilbert => hilbert(cols, rows),
+    };
+
+    coords
+        .into_iter()
+        .map(|(tx, ty)| Tile {
+            x0: tx * tile_size,
+            y0: ty * tile_size,
+            x1: ((tx + 1) * tile_size).min(width),
+            y1: ((ty + 1) * tile_size).min(height),
+        })
+        .collect()
+}
+
+// Walks a square spiral out from the centre tile, keeping the steps that land inside the grid.
+fn spiral(cols: u32, rows: u32) -> Vec<(u32, u32)> {
+    let total = cols as usize * rows as usize;
+    let mut coords = Vec::with_capacity(total);
+    let (mut x, mut y) = (((cols as i64) - 1) / 2, ((rows as i64) - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 0;
+
+    let visit = |x: i64, y: i64, coords: &mut Vec<(u32, u32)>| {
+        if (0..cols as i64).contains(&x) && (0..rows as i64).contains(&y) {
+            coords.push((x as u32, y as u32));
+        }
+    };
+
+    visit(x, y, &mut coords);
+    while coords.len() < total {
+        let (dx, dy) = directions[leg % 4];
+        let steps = leg / 2 + 1;
+        for _ in 0..steps {
+            x += dx;
+            y += dy;
+            visit(x, y, &mut coords);
+        }
+        leg += 1;
+    }
+
+    coords
+}
+
+// Orders tiles along a Hilbert curve over the smallest power-of-two square covering the grid.
+fn hilbert(cols: u32, rows: u32) -> Vec<(u32, u32)> {
+    let n = cols.max(rows).max(1).next_power_of_two();
+    (0..n as u64 * n as u64)
+        .map(|d| hilbert_d2xy(n, d))
+        .filter(|&(x, y)| x < cols && y < rows)
+        .collect()
+}
+
+fn hilbert_d2xy(n: u32, d: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0u64, 0u64);
+    let mut t = d;
+    let mut s = 1u64;
+    while s < n as u64 {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x as u32, y as u32)
+}
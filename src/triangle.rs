@@ -0,0 +1,183 @@
+use crate::*;
+
+// A single triangle. Without per-vertex UVs, (u, v) are the barycentric coordinates of the hit
+// with respect to the second and third vertices; with per-vertex normals, shading uses the
+// interpolated normal instead of the flat one.
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<dyn Material>,
+    bbox: AABB,
+    normal: Vec3,
+    area: f64,
+    id: u32,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point3; 3], material: Arc<dyn Material>) -> Self {
+        Self::with_attributes(vertices, None, None, material)
+    }
+
+    pub fn with_attributes(
+        vertices: [Point3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let n = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+        Self {
+            vertices,
+            normals,
+            uvs,
+            material,
+            bbox: triangle_bounding_box(&vertices),
+            normal: n.unit_vector(),
+            area: 0.5 * n.length(),
+            id: next_object_id(),
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        let Some((t, b1, b2)) = intersect_triangle(&self.vertices, ray, ray_t) else {
+            return false;
+        };
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.material = self.material.clone();
+        rec.object_id = self.id;
+        rec.vertex_colour = Colour::one();
+        set_triangle_surface(
+            rec,
+            ray,
+            self.normal,
+            [1.0 - b1 - b2, b1, b2],
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+        );
+
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::new(*origin, *direction, 0.0),
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+            &mut Rng::new(0),
+        ) {
+            return 0.0;
+        }
+
+        // Convert the uniform density over the triangle's area into one over solid angle.
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(self.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        random_triangle_point(&self.vertices, sampler) - *origin
+    }
+}
+
+pub(crate) fn triangle_bounding_box(vertices: &[Point3; 3]) -> AABB {
+    AABB::from_boxes(
+        &AABB::from_points(&vertices[0], &vertices[1]),
+        &AABB::from_points(&vertices[2], &vertices[2]),
+    )
+}
+
+// Folding the unit square along its diagonal gives a uniform point on the triangle.
+pub(crate) fn random_triangle_point(vertices: &[Point3; 3], sampler: &mut dyn Sampler) -> Point3 {
+    let (mut a, mut b) = sampler.get_2d();
+    if a + b > 1.0 {
+        (a, b) = (1.0 - a, 1.0 - b);
+    }
+    let [v0, v1, v2] = *vertices;
+    v0 + (v1 - v0) * a + (v2 - v0) * b
+}
+
+// Möller-Trumbore: the ray parameter of the hit and its barycentric coordinates with respect to
+// the second and third vertices.
+pub(crate) fn intersect_triangle(
+    vertices: &[Point3; 3],
+    ray: &Ray,
+    ray_t: Interval,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = vertices[1] - vertices[0];
+    let edge2 = vertices[2] - vertices[0];
+    let p = ray.direction().cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = ray.origin() - vertices[0];
+    let b1 = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let b2 = ray.direction().dot(q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_det;
+    ray_t.contains(t).then_some((t, b1, b2))
+}
+
+// Fills in the normal and texture coordinates of a hit at the given barycentric coordinates.
+// Which side was hit follows the flat normal; an interpolated normal is turned to that side, so
+// vertex normals wound the other way round from the triangle still shade correctly. Normals
+// that cancel out leave the flat one.
+pub(crate) fn set_triangle_surface(
+    rec: &mut HitRecord,
+    ray: &Ray,
+    flat_normal: Vec3,
+    barycentric: [f64; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+) {
+    let [w0, w1, w2] = barycentric;
+    rec.set_face_normal(ray, flat_normal);
+
+    if let Some(normals) = normals {
+        let interpolated = normals[0] * w0 + normals[1] * w1 + normals[2] * w2;
+        if !interpolated.near_zero() {
+            let mut shading_normal = interpolated.unit_vector();
+            if shading_normal.dot(flat_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            rec.normal = if rec.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+    }
+
+    (rec.u, rec.v) = match uvs {
+        Some(uvs) => (
+            uvs[0].0 * w0 + uvs[1].0 * w1 + uvs[2].0 * w2,
+            uvs[0].1 * w0 + uvs[1].1 * w1 + uvs[2].1 * w2,
+        ),
+        None => (w1, w2),
+    };
+}
@@ -0,0 +1,252 @@
+use crate::*;
+
+// Vertex buffers shared by every triangle of a mesh.
+struct MeshData {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colours: Option<Vec<Colour>>,
+    material: Arc<dyn Material>,
+    // Total area of the kept faces, over which light sampling picks points uniformly.
+    area: f64,
+    id: u32,
+}
+
+// One face of a mesh: three indices into the shared buffers.
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    indices: [usize; 3],
+    bbox: AABB,
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [Point3; 3] {
+        self.indices.map(|i| self.mesh.positions[i])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        let vertices = self.vertices();
+        let Some((t, b1, b2)) = intersect_triangle(&vertices, ray, ray_t) else {
+            return false;
+        };
+
+        let flat_normal = (vertices[1] - vertices[0])
+            .cross(vertices[2] - vertices[0])
+            .unit_vector();
+        let normals = self
+            .mesh
+            .normals
+            .as_ref()
+            .map(|n| self.indices.map(|i| n[i]));
+        let uvs = self.mesh.uvs.as_ref().map(|uv| self.indices.map(|i| uv[i]));
+        let barycentric = [1.0 - b1 - b2, b1, b2];
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.material = self.mesh.material.clone();
+        rec.object_id = self.mesh.id;
+        rec.vertex_colour = self.mesh.colours.as_ref().map_or(Colour::one(), |colours| {
+            (0..3).fold(Colour::zero(), |sum, k| {
+                sum + colours[self.indices[k]] * barycentric[k]
+            })
+        });
+        set_triangle_surface(
+            rec,
+            ray,
+            flat_normal,
+            barycentric,
+            normals.as_ref(),
+            uvs.as_ref(),
+        );
+
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    // This face's share of the mesh's density: the mesh picks points uniformly over its whole
+    // area, so a direction crossing several faces sums their shares (see `BVHNode::pdf_value`).
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let vertices = self.vertices();
+        let ray = Ray::new(*origin, *direction, 0.0);
+        let Some((t, _, _)) =
+            intersect_triangle(&vertices, &ray, Interval::new(0.001, f64::INFINITY))
+        else {
+            return 0.0;
+        };
+
+        let normal = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+        let distance_squared = t * t * direction.length_squared();
+        let cosine = (direction.dot(normal) / (direction.length() * normal.length())).abs();
+        distance_squared / (cosine * self.mesh.area)
+    }
+}
+
+// An indexed triangle mesh with one material. Its faces share the vertex buffers and sit in a
+// BVH of their own, so the whole mesh is a single object to the scene and its object ID.
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: Option<BVHNode>,
+    bbox: AABB,
+    // The kept faces, and the running total of their areas for picking one to sample.
+    faces: Vec<[usize; 3]>,
+    cumulative_areas: Vec<f64>,
+}
+
+impl TriangleMesh {
+    // `normals`, `uvs` and `colours`, when given, run parallel to `positions`. The colours
+    // tint the material. Faces with no area are dropped.
+    //
+    // Fails if a face indexes past the end of a vertex buffer, or if `normals`, `uvs` or
+    // `colours` is shorter than `positions`; loaders report the message as a malformed model.
+    pub fn new(
+        positions: Vec<Point3>,
+        faces: &[[usize; 3]],
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        colours: Option<Vec<Colour>>,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, String> {
+        for (name, len) in [
+            ("normals", normals.as_ref().map(Vec::len)),
+            ("uvs", uvs.as_ref().map(Vec::len)),
+            ("colours", colours.as_ref().map(Vec::len)),
+        ] {
+            if let Some(len) = len.filter(|&len| len < positions.len()) {
+                return Err(format!(
+                    "mesh has {} positions but only {} {}",
+                    positions.len(),
+                    len,
+                    name
+                ));
+            }
+        }
+        if let Some((face, index)) = faces.iter().enumerate().find_map(|(face, indices)| {
+            indices
+                .iter()
+                .find(|&&i| i >= positions.len())
+                .map(|&i| (face, i))
+        }) {
+            return Err(format!(
+                "face {} refers to vertex {} of {}",
+                face,
+                index,
+                positions.len()
+            ));
+        }
+
+        let mut kept = Vec::with_capacity(faces.len());
+        let mut cumulative_areas = Vec::with_capacity(faces.len());
+        let mut area = 0.0;
+        for &indices in faces {
+            let [a, b, c] = indices.map(|i| positions[i]);
+            let face_area = 0.5 * (b - a).cross(c - a).length();
+            if face_area == 0.0 {
+                continue;
+            }
+            area += face_area;
+            kept.push(indices);
+            cumulative_areas.push(area);
+        }
+
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            colours,
+            material,
+            area,
+            id: next_object_id(),
+        });
+
+        let mut objects: Vec<Arc<dyn Hittable>> = kept
+            .iter()
+            .map(|&indices| {
+                let triangle: Arc<dyn Hittable> = Arc::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    indices,
+                    bbox: triangle_bounding_box(&indices.map(|i| mesh.positions[i])),
+                });
+                triangle
+            })
+            .collect();
+
+        let triangles = objects.len();
+        let bvh = (triangles > 0).then(|| BVHNode::new(&mut objects, 0, triangles));
+        let bbox = bvh.as_ref().map_or(AABB::empty(), Hittable::bounding_box);
+        Ok(Self {
+            mesh,
+            bvh,
+            bbox,
+            faces: kept,
+            cumulative_areas,
+        })
+    }
+
+    // Number of faces kept after dropping degenerate ones.
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.mesh.material.is_emissive()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.bvh
+            .as_ref()
+            .is_some_and(|bvh| bvh.hit(ray, ray_t, rec, sampler))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray, ray_t: Interval, sampler: &mut dyn Sampler) -> u64 {
+        self.bvh
+            .as_ref()
+            .map_or(0, |bvh| bvh.bvh_nodes_visited(ray, ray_t, sampler))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.bvh
+            .as_ref()
+            .map_or(0.0, |bvh| bvh.pdf_value(origin, direction))
+    }
+
+    // A face chosen in proportion to its area, then a uniform point on it.
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let Some(&area) = self.cumulative_areas.last() else {
+            return Vec3::new(1.0, 0.0, 0.0);
+        };
+        let target = sampler.get_1d() * area;
+        let index = self
+            .cumulative_areas
+            .partition_point(|&total| total <= target)
+            .min(self.faces.len() - 1);
+        let vertices = self.faces[index].map(|i| self.mesh.positions[i]);
+        random_triangle_point(&vertices, sampler) - *origin
+    }
+}
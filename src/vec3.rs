@@ -20,16 +20,12 @@ impl Vec3 {
         Self::new(1.0, 1.0, 1.0)
     }
 
-    pub fn random() -> Self {
-        Self::new(random_double(), random_double(), random_double())
+    pub fn random(sampler: &mut (impl Sampler + ?Sized)) -> Self {
+        Self::new(sampler.get_1d(), sampler.get_1d(), sampler.get_1d())
     }
 
-    pub fn random_range(min: f64, max: f64) -> Self {
-        Self::new(
-            random_double_range(min, max),
-            random_double_range(min, max),
-            random_double_range(min, max),
-        )
+    pub fn random_range(sampler: &mut (impl Sampler + ?Sized), min: f64, max: f64) -> Self {
+        Self::random(sampler) * (max - min) + min
     }
 
     pub fn x(self) -> f64 {
@@ -68,18 +64,18 @@ impl Vec3 {
         self / self.length()
     }
 
-    pub fn random_unit_vector() -> Self {
-        loop {
-            let p = Self::random_range(-1.0, 1.0);
-            let lensq = p.length_squared();
-            if 1e-160 < lensq && lensq <= 1.0 {
-                return p / lensq.sqrt();
-            }
-        }
+    // The sampling helpers map a fixed number of sample dimensions directly instead of rejection
+    // sampling, so stratified and low-discrepancy samplers stay aligned from one bounce to the next.
+    pub fn random_unit_vector(sampler: &mut (impl Sampler + ?Sized)) -> Self {
+        let (u, v) = sampler.get_2d();
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        Self::new(r * phi.cos(), r * phi.sin(), z)
     }
 
-    pub fn random_on_hemisphere(normal: &Self) -> Self {
-        let on_unit_sphere = Self::random_unit_vector();
+    pub fn random_on_hemisphere(sampler: &mut (impl Sampler + ?Sized), normal: &Self) -> Self {
+        let on_unit_sphere = Self::random_unit_vector(sampler);
         if on_unit_sphere.dot(*normal) > 0.0 {
             on_unit_sphere
         } else {
@@ -87,17 +83,27 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        loop {
-            let p = Self::new(
-                random_double_range(-1.0, 1.0),
-                random_double_range(-1.0, 1.0),
-                0.0,
-            );
-            if p.length_squared() < 1.0 {
-                return p;
-            }
+    // Cosine-weighted direction about +z.
+    pub fn random_cosine_direction(sampler: &mut (impl Sampler + ?Sized)) -> Self {
+        let (r1, r2) = sampler.get_2d();
+        let phi = 2.0 * PI * r1;
+        let r = r2.sqrt();
+        Self::new(phi.cos() * r, phi.sin() * r, (1.0 - r2).sqrt())
+    }
+
+    // Shirley and Chiu's concentric mapping from the square to the disk.
+    pub fn random_in_unit_disk(sampler: &mut (impl Sampler + ?Sized)) -> Self {
+        let (u, v) = sampler.get_2d();
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Self::zero();
         }
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, PI / 4.0 * (b / a))
+        } else {
+            (b, PI / 2.0 - PI / 4.0 * (a / b))
+        };
+        Self::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     pub fn near_zero(self) -> bool {
@@ -281,8 +287,6 @@ impl Neg for Vec3 {
 
 use std::ops::{AddAssign, DivAssign, MulAssign, SubAssign};
 
-use crate::random_double;
-
 impl AddAssign for Vec3 {
     fn add_assign(&mut self, rhs: Self) {
         *self = *self + rhs;